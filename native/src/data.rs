@@ -1,14 +1,17 @@
 use std::{
-    collections::HashMap,
-    fmt::Display,
+    collections::BTreeSet,
+    fmt::{Display, Write as _},
+    hash::{Hash, Hasher},
     num::NonZeroU32,
-    ops::{Mul, Range},
+    ops::{Add, AddAssign, Bound, Mul, Range},
     str::FromStr,
     sync::LazyLock,
 };
 
 use anyhow::{bail, ensure};
+use caseless::Caseless;
 use num::BigUint;
+use unicode_normalization::UnicodeNormalization;
 
 pub type BookingId = u64;
 pub type CustomerId = u64;
@@ -18,6 +21,16 @@ pub type RoomId = u64;
 pub struct HotelId(NonZeroU32);
 impl HotelId {
     const MAX_DIGITS: usize = 5;
+
+    pub fn get(self) -> u32 {
+        self.0.get()
+    }
+}
+impl TryFrom<u32> for HotelId {
+    type Error = anyhow::Error;
+    fn try_from(id: u32) -> Result<Self, Self::Error> {
+        Self::from_str(&id.to_string())
+    }
 }
 impl FromStr for HotelId {
     type Err = anyhow::Error;
@@ -40,23 +53,169 @@ impl Display for HotelId {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Currency([u8; Self::LEN]);
+impl Currency {
+    const LEN: usize = 3;
+    pub const EUR: Self = Self(*b"EUR");
+}
+impl Default for Currency {
+    fn default() -> Self {
+        Self::EUR
+    }
+}
+impl FromStr for Currency {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = <[u8; Self::LEN]>::try_from(s.as_bytes()).ok();
+        match code {
+            Some(code) if code.iter().all(u8::is_ascii_uppercase) => Ok(Self(code)),
+            _ => bail!("Invalid currency code '{s}'"),
+        }
+    }
+}
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Only ever built from ASCII letters
+        f.write_str(std::str::from_utf8(&self.0).unwrap())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ExchangeRate {
+    digits: BigUint,
+    scale: u32,
+}
+impl FromStr for ExchangeRate {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        ensure!(
+            !whole.is_empty()
+                && (whole.bytes().chain(fraction.bytes())).all(|b| b.is_ascii_digit()),
+            "Invalid exchange rate '{s}'",
+        );
+        let digits = BigUint::from_str(&format!("{whole}{fraction}"))?;
+        ensure!(digits != BigUint::ZERO, "Exchange rate must be non-zero");
+        Ok(Self {
+            digits,
+            scale: fraction.len() as u32,
+        })
+    }
+}
+impl Display for ExchangeRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.digits.to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{whole}.{fraction}")
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Price {
     pub cents: num::BigUint,
+    pub currency: Currency,
 }
 impl Price {
     const UNIT: char = '€';
     const SEPARATOR: char = '.';
     const MAX_SMALL_DIGITS: usize = 2;
     const SMALL_TO_BIG: u32 = u32::pow(10, Self::MAX_SMALL_DIGITS as u32);
+
+    pub fn zero(currency: Currency) -> Self {
+        Self {
+            cents: BigUint::ZERO,
+            currency,
+        }
+    }
+    pub fn is_zero(&self) -> bool {
+        self.cents == BigUint::ZERO
+    }
+    /// The sum of `prices`, which must all be in `currency`.
+    pub fn sum<'a>(currency: Currency, prices: impl IntoIterator<Item = &'a Price>) -> Self {
+        prices
+            .into_iter()
+            .fold(Self::zero(currency), |sum, price| sum + price)
+    }
+    pub fn checked_sub(&self, other: &Price) -> Option<Self> {
+        self.assert_same_currency(other);
+        (self.cents >= other.cents).then(|| Self {
+            cents: &self.cents - &other.cents,
+            currency: self.currency,
+        })
+    }
+    /// Rounded half up to whole cents.
+    pub fn percent(&self, percent: u32) -> Self {
+        Self {
+            cents: (&self.cents * percent + 50u32) / 100u32,
+            currency: self.currency,
+        }
+    }
+    /// Rounded half up to whole cents.
+    pub fn exchange(&self, from: &ExchangeRate, currency: Currency, to: &ExchangeRate) -> Self {
+        let ten = BigUint::from(10u32);
+        let numerator = &self.cents * &from.digits * ten.pow(to.scale);
+        let denominator = ten.pow(from.scale) * &to.digits;
+        Self {
+            cents: (numerator * 2u32 + &denominator) / (denominator * 2u32),
+            currency,
+        }
+    }
+
+    /// Prices are only ever combined within a hotel, which charges in a single currency, so
+    /// mixing currencies is a bug rather than an input error.
+    fn assert_same_currency(&self, other: &Price) {
+        assert_eq!(
+            self.currency, other.currency,
+            "Cannot combine prices in different currencies",
+        );
+    }
+}
+impl Add<&Price> for Price {
+    type Output = Price;
+    fn add(mut self, other: &Price) -> Price {
+        self += other;
+        self
+    }
+}
+impl AddAssign<&Price> for Price {
+    fn add_assign(&mut self, other: &Price) {
+        self.assert_same_currency(other);
+        self.cents += &other.cents;
+    }
+}
+impl Mul<usize> for &Price {
+    type Output = Price;
+    fn mul(self, count: usize) -> Price {
+        Price {
+            cents: &self.cents * count,
+            currency: self.currency,
+        }
+    }
 }
 
 impl FromStr for Price {
     type Err = anyhow::Error;
-    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        if s.ends_with(Self::UNIT) {
-            s = &s[..s.len() - 1];
-        }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The unit is either the euro sign or a currency code, and defaults to euros
+        let (s, currency) = match s.strip_suffix(Self::UNIT) {
+            Some(rest) => (rest, Currency::EUR),
+            None => match s
+                .len()
+                .checked_sub(Currency::LEN)
+                .and_then(|at| s.split_at_checked(at))
+            {
+                Some((rest, code)) if code.bytes().all(|b| b.is_ascii_alphabetic()) => {
+                    (rest, code.parse()?)
+                }
+                _ => (s, Currency::EUR),
+            },
+        };
         let mut parts = s.split(Self::SEPARATOR);
 
         let [lhs, rhs] = std::array::from_fn(|_| parts.next().unwrap_or(""));
@@ -65,6 +224,11 @@ impl FromStr for Price {
             parts.next().is_none(),
             "Cannot have multiple decimal seperators in price",
         );
+        // Parsing the numbers alone would also accept signs
+        ensure!(
+            !lhs.is_empty() && lhs.bytes().chain(rhs.bytes()).all(|b| b.is_ascii_digit()),
+            "Invalid price '{s}'",
+        );
         let big = BigUint::from_str(lhs)? * Self::SMALL_TO_BIG;
         let small = match rhs.len() {
             0 => 0,
@@ -85,59 +249,534 @@ impl FromStr for Price {
         };
         let cents = big + small;
         ensure!(cents != BigUint::ZERO, "Price must be non-zero");
-        Ok(Self { cents })
+        Ok(Self { cents, currency })
     }
 }
 impl Display for Price {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{big}{sep}{small}{unit}",
+            "{big}{sep}{small:0>width$}",
             big = &self.cents / Self::SMALL_TO_BIG,
             sep = Self::SEPARATOR,
             small = &self.cents % Self::SMALL_TO_BIG,
-            unit = Self::UNIT,
+            width = Self::MAX_SMALL_DIGITS,
+        )?;
+        match self.currency {
+            Currency::EUR => write!(f, "{}", Self::UNIT),
+            currency => write!(f, "{currency}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    Strict,
+    German,
+    English,
+}
+impl Locale {
+    fn separators(self) -> Option<(char, char)> {
+        match self {
+            Self::Strict => None,
+            Self::German => Some((',', '.')),
+            Self::English => Some(('.', ',')),
+        }
+    }
+}
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "strict" => Self::Strict,
+            "de-DE" => Self::German,
+            "en-US" => Self::English,
+            _ => bail!("Unknown locale '{s}'"),
+        })
+    }
+}
+impl Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Strict => "strict",
+                Self::German => "de-DE",
+                Self::English => "en-US",
+            }
         )
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Category {
-    Single,
-    Double,
-    Suite,
+impl Price {
+    pub fn parse_localized(s: &str, locale: Locale) -> anyhow::Result<Self> {
+        let Some((decimal, thousands)) = locale.separators() else {
+            return s.parse();
+        };
+        let s = s.trim();
+        let (amount, currency) = if let Some(rest) = s.strip_prefix(Self::UNIT) {
+            (rest, Currency::EUR)
+        } else if let Some(rest) = s.strip_suffix(Self::UNIT) {
+            (rest, Currency::EUR)
+        } else {
+            match (
+                s.get(..Currency::LEN),
+                s.get(s.len().saturating_sub(Currency::LEN)..),
+            ) {
+                (Some(code), _) if code.bytes().all(|b| b.is_ascii_alphabetic()) => {
+                    (&s[Currency::LEN..], code.parse()?)
+                }
+                (_, Some(code)) if code.bytes().all(|b| b.is_ascii_alphabetic()) => {
+                    (&s[..s.len() - Currency::LEN], code.parse()?)
+                }
+                _ => (s, Currency::EUR),
+            }
+        };
+        let amount = amount.trim();
+
+        let (whole, fraction) = amount.split_once(decimal).unwrap_or((amount, ""));
+        let mut groups = whole.split(thousands);
+        let first = groups.next().unwrap_or_default();
+        let grouped = groups.clone().next().is_some();
+        let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+        ensure!(
+            digits(first)
+                && (!grouped || first.len() <= 3)
+                && groups
+                    .clone()
+                    .all(|group| group.len() == 3 && digits(group))
+                && (fraction.is_empty() || digits(fraction)),
+            "Invalid price '{s}' for locale {locale}",
+        );
+        let whole: String = whole.split(thousands).collect();
+        format!("{whole}{}{fraction}{currency}", Self::SEPARATOR).parse()
+    }
+    pub fn localized(&self, locale: Locale) -> LocalizedPrice<'_> {
+        LocalizedPrice {
+            price: self,
+            locale,
+        }
+    }
+}
+
+pub struct LocalizedPrice<'a> {
+    price: &'a Price,
+    locale: Locale,
+}
+impl Display for LocalizedPrice<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { price, locale } = *self;
+        let Some((decimal, thousands)) = locale.separators() else {
+            return write!(f, "{price}");
+        };
+        let whole = (&price.cents / Price::SMALL_TO_BIG).to_string();
+        let mut amount = String::new();
+        for (idx, digit) in whole.chars().enumerate() {
+            if idx > 0 && (whole.len() - idx).is_multiple_of(3) {
+                amount.push(thousands);
+            }
+            amount.push(digit);
+        }
+        write!(
+            amount,
+            "{decimal}{small:0>width$}",
+            small = &price.cents % Price::SMALL_TO_BIG,
+            width = Price::MAX_SMALL_DIGITS,
+        )?;
+        match (locale, price.currency) {
+            (Locale::English, Currency::EUR) => write!(f, "{}{amount}", Price::UNIT),
+            (Locale::English, currency) => write!(f, "{currency} {amount}"),
+            (_, Currency::EUR) => write!(f, "{amount} {}", Price::UNIT),
+            (_, currency) => write!(f, "{amount} {currency}"),
+        }
+    }
+}
+
+/// Parsing only checks the spelling, the model checks that it knows the category.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Category(String);
+impl Category {
+    const BUILTIN: [(&str, u32); 3] = [("Single", 1), ("Double", 2), ("Suite", 4)];
+
+    pub fn builtin() -> impl Iterator<Item = (Self, u32)> {
+        Self::BUILTIN
+            .iter()
+            .map(|&(name, capacity)| (Self(name.into()), capacity))
+    }
+    pub fn builtin_capacity(&self) -> Option<u32> {
+        Self::BUILTIN
+            .iter()
+            .find(|&&(name, _)| name == self.0)
+            .map(|&(_, capacity)| capacity)
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 impl FromStr for Category {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ensure!(
+            s.starts_with(char::is_alphabetic)
+                && s.chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_'),
+            "Invalid category '{s}'",
+        );
+        // Would be mistaken for the scope of every room
+        ensure!(s != RuleScope::ALL, "'{s}' cannot be a category");
+        Ok(Self(s.into()))
+    }
+}
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Turnover {
+    /// Stays are half-open: a guest may arrive on the day the previous guest leaves.
+    #[default]
+    SameDay,
+    /// The room is blocked on the day a stay ends, so stays must not touch.
+    NextDay,
+}
+impl Turnover {
+    pub fn start_bound(self, end: &Date) -> Bound<&Date> {
+        match self {
+            Self::SameDay => Bound::Excluded(end),
+            Self::NextDay => Bound::Included(end),
+        }
+    }
+    pub fn conflicts(self, booked: &Range<Date>, Range { start, end }: Range<&Date>) -> bool {
+        match self {
+            Self::SameDay => booked.start < *end && *start < booked.end,
+            Self::NextDay => booked.start <= *end && *start <= booked.end,
+        }
+    }
+}
+impl FromStr for Turnover {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
-            "Single" => Self::Single,
-            "Double" => Self::Double,
-            "Suite" => Self::Suite,
-            _ => bail!("Unknown category '{s}'"),
+            "same-day" => Self::SameDay,
+            "next-day" => Self::NextDay,
+            _ => bail!("Unknown turnover policy '{s}'"),
         })
     }
 }
-impl Display for Category {
+impl Display for Turnover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::SameDay => "same-day",
+                Self::NextDay => "next-day",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RateKind {
+    HighSeason,
+    LowSeason,
+    /// Takes precedence over seasons.
+    Event,
+}
+impl RateKind {
+    /// Periods of kinds in the same group must not overlap.
+    pub fn is_season(self) -> bool {
+        matches!(self, Self::HighSeason | Self::LowSeason)
+    }
+}
+impl FromStr for RateKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "high" => Self::HighSeason,
+            "low" => Self::LowSeason,
+            "event" => Self::Event,
+            _ => bail!("Unknown rate kind '{s}'"),
+        })
+    }
+}
+impl Display for RateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::HighSeason => "high",
+                Self::LowSeason => "low",
+                Self::Event => "event",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RatePeriod {
+    pub kind: RateKind,
+    pub time: Range<Date>,
+    pub price: Price,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Percentage(i32);
+impl Percentage {
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+impl FromStr for Percentage {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(number) = s.strip_suffix('%') else {
+            bail!("Percentage '{s}' must end with %")
+        };
+        let percent = i32::from_str(number)?;
+        ensure!(percent >= -100, "Cannot take away more than 100%");
+        Ok(Self(percent))
+    }
+}
+impl Display for Percentage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:+}%", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleCondition {
+    /// Friday and Saturday nights.
+    Weekend,
+    /// Every night of stays that are at least this many nights long.
+    MinNights(u32),
+}
+impl FromStr for RuleCondition {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "weekend" {
+            return Ok(Self::Weekend);
+        }
+        match s.strip_suffix('+').map(u32::from_str) {
+            Some(Ok(nights)) if nights > 0 => Ok(Self::MinNights(nights)),
+            _ => bail!("Unknown rule condition '{s}', expected weekend or <nights>+"),
+        }
+    }
+}
+impl Display for RuleCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Weekend => write!(f, "weekend"),
+            Self::MinNights(nights) => write!(f, "{nights}+"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleScope {
+    AllRooms,
+    Category(Category),
+}
+impl RuleScope {
+    const ALL: &str = "all";
+
+    pub fn includes(&self, category: &Category) -> bool {
+        match self {
+            Self::AllRooms => true,
+            Self::Category(scope) => scope == category,
+        }
+    }
+}
+impl FromStr for RuleScope {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            Self::ALL => Self::AllRooms,
+            _ => Self::Category(s.parse()?),
+        })
+    }
+}
+impl Display for RuleScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AllRooms => write!(f, "{}", Self::ALL),
+            Self::Category(category) => write!(f, "{category}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PricingRule {
+    pub name: String,
+    pub scope: RuleScope,
+    pub condition: RuleCondition,
+    pub adjustment: Percentage,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChargeKind {
+    /// Charged for every night and guest, such as a city tax.
+    Tax,
+    /// Charged once per stay, such as a cleaning fee.
+    Fee,
+}
+impl FromStr for ChargeKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "tax" => Self::Tax,
+            "fee" => Self::Fee,
+            _ => bail!("Unknown charge kind '{s}'"),
+        })
+    }
+}
+impl Display for ChargeKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}",
             match self {
-                Self::Single => "Single",
-                Self::Double => "Double",
-                Self::Suite => "Suite",
+                Self::Tax => "tax",
+                Self::Fee => "fee",
             }
         )
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Charge {
+    pub name: String,
+    pub kind: ChargeKind,
+    pub scope: RuleScope,
+    pub amount: Price,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discount {
+    Percent(u32),
+    Fixed(Price),
+}
+impl FromStr for Discount {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(number) = s.strip_suffix('%') {
+            let percent = u32::from_str(number)?;
+            ensure!(
+                (1..=100).contains(&percent),
+                "Percent discounts must be between 1% and 100%",
+            );
+            return Ok(Self::Percent(percent));
+        }
+        Ok(Self::Fixed(s.parse()?))
+    }
+}
+impl Discount {
+    pub fn parse_localized(s: &str, locale: Locale) -> anyhow::Result<Self> {
+        if s.ends_with('%') {
+            return s.parse();
+        }
+        Ok(Self::Fixed(Price::parse_localized(s, locale)?))
+    }
+}
+impl Display for Discount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Percent(percent) => write!(f, "{percent}%"),
+            Self::Fixed(price) => write!(f, "{price}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsageLimit {
+    Unlimited,
+    Uses(u64),
+}
+impl UsageLimit {
+    pub fn allows(self, used: usize) -> bool {
+        match self {
+            Self::Unlimited => true,
+            Self::Uses(max) => (used as u64) < max,
+        }
+    }
+}
+impl FromStr for UsageLimit {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "unlimited" => Self::Unlimited,
+            _ => Self::Uses(s.parse()?),
+        })
+    }
+}
+impl Display for UsageLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unlimited => write!(f, "unlimited"),
+            Self::Uses(max) => write!(f, "{max}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PromoCode {
+    pub code: String,
+    pub discount: Discount,
+    /// The arrival dates of the stays that may use the code.
+    pub valid: Range<Date>,
+    pub limit: UsageLimit,
+    pub customer_limit: UsageLimit,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct AppliedPromo {
+    pub code: String,
+    pub discount: Price,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RemovalPolicy {
+    #[default]
+    Refuse,
+    /// Cancel the future bookings and report them.
+    Cascade,
+    /// Drop the bookings together with the room without reporting them.
+    Force,
+}
+impl FromStr for RemovalPolicy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "refuse" => Self::Refuse,
+            "cascade" => Self::Cascade,
+            "force" => Self::Force,
+            _ => bail!("Unknown removal policy '{s}'"),
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Date(time::Date);
 impl Date {
-    pub fn into_inner(self) -> time::Date {
-        let Self(date) = self;
-        date
+    pub fn today() -> Self {
+        Self(time::OffsetDateTime::now_utc().date())
+    }
+    pub fn is_weekend_night(&self) -> bool {
+        matches!(
+            self.0.weekday(),
+            time::Weekday::Friday | time::Weekday::Saturday
+        )
+    }
+    pub fn nights_until<'a>(&self, end: &'a Date) -> impl Iterator<Item = Date> + use<'a> {
+        std::iter::successors(Some(self.0), |night| night.next_day())
+            .take_while(|night| *night < end.0)
+            .map(Self)
     }
 }
 
@@ -155,35 +794,325 @@ impl Display for Date {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay(time::Time);
+static TIME_FORMAT: LazyLock<time::format_description::OwnedFormatItem> =
+    LazyLock::new(|| time::format_description::parse_owned::<2>("[hour]:[minute]").unwrap());
+impl FromStr for TimeOfDay {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(time::Time::parse(s, &TIME_FORMAT)?))
+    }
+}
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format(&TIME_FORMAT).unwrap())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Stars(u8);
+impl Stars {
+    const MAX: u8 = 5;
+}
+impl FromStr for Stars {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stars = s.parse()?;
+        ensure!(
+            (1..=Self::MAX).contains(&stars),
+            "Hotels have between 1 and {} stars",
+            Self::MAX,
+        );
+        Ok(Self(stars))
+    }
+}
+impl Display for Stars {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The spelling that names and cities are compared by, ignoring case and how accents are encoded.
+pub fn fold(text: &str) -> String {
+    text.nfd().default_case_fold().nfc().collect()
+}
+
+#[derive(Clone, Debug)]
 pub struct HotelData {
+    /// Matched by its [`fold`]ed spelling.
     pub city: String,
-    pub rooms: HashMap<u64, RoomData>,
+    /// The currency of every room price in the hotel.
+    pub currency: Currency,
+    pub details: HotelDetails,
 }
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(Clone, Debug, Default)]
+pub struct HotelDetails {
+    pub name: Option<String>,
+    pub address: Option<String>,
+    pub stars: Option<Stars>,
+    pub check_in: Option<TimeOfDay>,
+    pub check_out: Option<TimeOfDay>,
+    pub contact: Option<String>,
+}
+/// Two persons are equal if their names are spelled the same after they are [`fold`]ed.
+#[derive(Debug, Clone)]
 pub struct Person {
     pub forename: String,
     pub surname: String,
 }
+impl Person {
+    fn key(&self) -> (String, String) {
+        (fold(&self.forename), fold(&self.surname))
+    }
+}
+impl PartialEq for Person {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+impl Eq for Person {}
+impl Hash for Person {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Customer {
+    pub person: Person,
+    pub details: CustomerDetails,
+}
+#[derive(Clone, Debug, Default)]
+pub struct CustomerDetails {
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub birth_date: Option<Date>,
+    pub address: Option<String>,
+}
 
+#[derive(Clone, Debug)]
+pub enum CustomerRef {
+    Id(CustomerId),
+    /// The customer with this name, who is added if there is none yet.
+    Name(Person),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CustomerKey {
+    None,
+    Name,
+    #[default]
+    Email,
+    Phone,
+    NameAndBirthDate,
+}
+impl CustomerKey {
+    /// Customers without the detail that the key is about never match.
+    pub fn matches(self, a: &Customer, b: &Customer) -> bool {
+        let (a_details, b_details) = (&a.details, &b.details);
+        match self {
+            Self::None => false,
+            Self::Name => a.person == b.person,
+            Self::Email => a_details
+                .email
+                .as_ref()
+                .zip(b_details.email.as_ref())
+                .is_some_and(|(a, b)| a.eq_ignore_ascii_case(b)),
+            Self::Phone => {
+                let digits = |phone: &String| -> String {
+                    phone.chars().filter(|c| !c.is_whitespace()).collect()
+                };
+                a_details
+                    .phone
+                    .as_ref()
+                    .zip(b_details.phone.as_ref())
+                    .is_some_and(|(a, b)| digits(a) == digits(b))
+            }
+            Self::NameAndBirthDate => {
+                a.person == b.person
+                    && a_details.birth_date.is_some()
+                    && a_details.birth_date == b_details.birth_date
+            }
+        }
+    }
+}
+impl FromStr for CustomerKey {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "none" => Self::None,
+            "name" => Self::Name,
+            "email" => Self::Email,
+            "phone" => Self::Phone,
+            "name-and-birth-date" => Self::NameAndBirthDate,
+            _ => bail!("Unknown customer key '{s}'"),
+        })
+    }
+}
+impl Display for CustomerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::None => "none",
+                Self::Name => "name",
+                Self::Email => "email",
+                Self::Phone => "phone",
+                Self::NameAndBirthDate => "name-and-birth-date",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct RoomData {
     pub category: Category,
     pub price: Price,
-    pub bookings: Vec<Booking>,
+    pub capacity: u32,
+    /// What each guest after the first adds to the price of a night.
+    pub supplement: Option<Price>,
+    pub amenities: BTreeSet<Amenity>,
+}
+
+/// Written in lowercase, other spellings are folded to it.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amenity(String);
+impl FromStr for Amenity {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ensure!(
+            s.starts_with(|c: char| c.is_ascii_alphabetic())
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+            "Invalid amenity '{s}'",
+        );
+        Ok(Self(s.to_ascii_lowercase()))
+    }
+}
+impl Display for Amenity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RoomFilter {
+    pub guests: u32,
+    /// Hotels without a rating never have enough.
+    pub min_stars: Option<Stars>,
+    pub required: BTreeSet<Amenity>,
+    pub excluded: BTreeSet<Amenity>,
+}
+impl RoomFilter {
+    pub fn accepts(&self, room: &RoomData) -> bool {
+        room.capacity >= self.guests
+            && self.required.is_subset(&room.amenities)
+            && self.excluded.is_disjoint(&room.amenities)
+    }
+    pub fn accepts_hotel(&self, hotel: &HotelData) -> bool {
+        // No rating is less than every rating
+        hotel.details.stars >= self.min_stars
+    }
 }
 
+#[derive(Clone, Debug)]
 pub struct Booking {
+    pub hotel: HotelId,
+    pub room: RoomId,
     pub time: Range<Date>,
     pub customer: CustomerId,
     pub id: BookingId,
+    pub guests: u32,
+    /// The nightly price of the room when it was booked.
+    pub rate: Price,
+    /// The price of the whole stay when it was booked, after any discount.
+    pub total: Price,
+    pub promo: Option<AppliedPromo>,
 }
 
-impl RoomData {
-    pub(crate) fn is_occupied(&self, Range { start, end }: Range<&Date>) -> bool {
-        for Booking { time, .. } in &self.bookings {
-            if time.contains(start) || time.contains(end) {
-                return true;
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeBounds;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn date(day: u8) -> Date {
+        Date(time::Date::from_ordinal_date(2030, 1).unwrap() + time::Duration::days(day.into()))
+    }
+    fn share_a_day(a: Range<u8>, b: Range<u8>, turnover: Turnover) -> bool {
+        match turnover {
+            Turnover::SameDay => a.clone().any(|night| b.contains(&night)),
+            Turnover::NextDay => (a.start..=a.end).any(|day| (b.start..=b.end).contains(&day)),
+        }
+    }
+    fn turnover() -> impl Strategy<Value = Turnover> {
+        prop_oneof![Just(Turnover::SameDay), Just(Turnover::NextDay)]
+    }
+    fn price() -> impl Strategy<Value = Price> {
+        (1..=u128::MAX, "[A-Z]{3}").prop_map(|(cents, currency)| Price {
+            cents: cents.into(),
+            currency: currency.parse().unwrap(),
+        })
+    }
+    fn locale() -> impl Strategy<Value = Locale> {
+        prop_oneof![
+            Just(Locale::Strict),
+            Just(Locale::German),
+            Just(Locale::English)
+        ]
+    }
+    fn parse(s: &str) -> Option<Price> {
+        s.parse().ok()
+    }
+
+    #[test]
+    fn non_canonical_prices_are_rejected_or_normalised() {
+        for s in [
+            "1.+5", "+1.05", "1.-5", "-1.05", "1.0+", " 1.05", "1 .05", ".5", "1.5.0", "0",
+        ] {
+            assert_eq!(parse(s), None, "{s}");
+        }
+        for (s, canonical) in [
+            ("1.5", "1.50€"),
+            ("01.05", "1.05€"),
+            ("1.", "1.00€"),
+            ("1.050", "1.05€"),
+            ("2CHF", "2.00CHF"),
+            ("2EUR", "2.00€"),
+        ] {
+            assert_eq!(
+                parse(s).map(|price| price.to_string()).as_deref(),
+                Some(canonical)
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn stays_conflict_if_they_share_a_day(
+            (a, b) in (0..40u8, 1..10u8, 0..40u8, 1..10u8)
+                .prop_map(|(a, a_len, b, b_len)| (a..a + a_len, b..b + b_len)),
+            turnover in turnover(),
+        ) {
+            let booked = date(a.start)..date(a.end);
+            let stay = date(b.start)..date(b.end);
+            let conflicts = turnover.conflicts(&booked, &stay.start..&stay.end);
+            prop_assert_eq!(conflicts, share_a_day(a, b, turnover));
+            if conflicts {
+                // Otherwise looking up the bookings before the bound would miss this one
+                prop_assert!(
+                    (Bound::Unbounded, turnover.start_bound(&stay.end)).contains(&&booked.start)
+                );
             }
         }
-        false
+
+        #[test]
+        fn prices_round_trip(price in price(), locale in locale()) {
+            let written = price.localized(locale).to_string();
+            prop_assert_eq!(Price::parse_localized(&written, locale).unwrap(), price);
+        }
     }
 }
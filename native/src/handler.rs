@@ -3,9 +3,15 @@ pub type Result<T = String, E = CommandError> = std::result::Result<T, E>;
 
 mod commands;
 
-use anyhow::bail;
+use std::path::Path;
 
-use crate::model::Model;
+use anyhow::{Context, bail};
+
+use crate::{
+    data::{Currency, Locale},
+    journal::{Contents, Journal},
+    model::Model,
+};
 
 pub struct CommandResult {
     pub command_output: String,
@@ -15,13 +21,53 @@ pub struct CommandResult {
 
 pub struct CommandHandler {
     model: Model,
+    journal: Option<Journal>,
+    locale: Locale,
+    /// The locale that replaying the journal up to its end would leave the session in.
+    journal_locale: Locale,
 }
 
 const QUIT_CMD: &str = "quit";
+/// Replaces the model with a snapshot, which starts the journal over from a checkpoint.
+const LOAD_CMD: &str = "load";
+/// Reads a file into the model, which is journaled as the command that sets what was read.
+const IMPORT_CMD: &str = "import";
+const LOCALE_CMD: &str = "locale";
+/// Commands that never change the model and are therefore not journaled.
+const READ_ONLY_CMDS: &[&str] = &["find", "list", "show", "quote", "save"];
 
 impl CommandHandler {
     pub fn new(model: Model) -> Self {
-        Self { model }
+        Self {
+            model,
+            journal: None,
+            locale: Locale::default(),
+            journal_locale: Locale::default(),
+        }
+    }
+    pub fn with_journal(mut model: Model, path: &Path) -> Result<Self> {
+        let (
+            journal,
+            Contents {
+                checkpoint,
+                records,
+            },
+        ) = Journal::open(path)?;
+        if let Some(checkpoint) = checkpoint {
+            model
+                .load(&checkpoint)
+                .context("Cannot restore journal checkpoint")?;
+        }
+        let mut handler = Self::new(model);
+        for (idx, record) in records.into_iter().enumerate() {
+            handler
+                .handle_command_split(record)
+                .with_context(|| format!("Cannot replay journal record {}", idx + 1))?;
+        }
+        handler.journal = Some(journal);
+        // Every session starts out strict, whatever locale the journal was written in
+        handler.journal_locale = std::mem::take(&mut handler.locale);
+        Ok(handler)
     }
     pub fn handle_command(&mut self, command: String) -> CommandResult {
         match self.handle_command_split(command) {
@@ -42,7 +88,12 @@ impl CommandHandler {
         let Some(command) = args.next() else {
             bail!("Please enter a command")
         };
-        self.handle_command_args(command, args.collect())
+        let args: Vec<&str> = args.collect();
+        let args = match (self.locale, commands::price_position(command, &args)) {
+            (Locale::Strict, _) | (_, None) => args.into_iter().map(String::from).collect(),
+            (_, Some(at)) => join_units(args, at),
+        };
+        self.handle_command_args(command, args.iter().map(String::as_str).collect())
     }
     fn handle_command_args(&mut self, command: &str, args: Vec<&str>) -> Result<(String, bool)> {
         match command {
@@ -52,12 +103,239 @@ impl CommandHandler {
                 }
                 Ok((Default::default(), true))
             }
+            LOCALE_CMD => match args[..] {
+                [] => Ok((self.locale.to_string(), false)),
+                [locale] => {
+                    self.locale = locale.parse()?;
+                    Ok(("OK".into(), false))
+                }
+                [_, arg, ..] => bail!("Unexpected argument {arg}"),
+            },
             _ => self
                 .handle_normal_command(command, args)
                 .map(|message| (message, false)),
         }
     }
     fn handle_normal_command(&mut self, command: &str, args: Vec<&str>) -> Result {
-        commands::handle(command, &mut self.model, args)
+        let record = (self.journal.is_some() && !READ_ONLY_CMDS.contains(&command)).then(|| {
+            [command]
+                .iter()
+                .chain(&args)
+                .copied()
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        let output = commands::handle(command, &mut self.model, args, self.locale)?;
+        let (Some(journal), Some(record)) = (&mut self.journal, record) else {
+            return Ok(output);
+        };
+        if command == LOAD_CMD {
+            // Replaying `load` would depend on the snapshot file still being the same
+            journal
+                .reset(|path| self.model.save(path))
+                .context("The snapshot was loaded, but the journal could not be started over")?;
+            // Replaying starts out strict again
+            self.journal_locale = Locale::default();
+        } else {
+            // Replaying an import would depend on the file still being the same
+            let (record, locale) = match command {
+                IMPORT_CMD => (
+                    commands::set_exchange_rates_command(&self.model)
+                        .context("The command was applied, but could not be journaled")?,
+                    Locale::Strict,
+                ),
+                _ => (record, self.locale),
+            };
+            // Replaying the record has to read its prices the same way
+            if self.journal_locale != locale {
+                journal
+                    .append(&format!("{LOCALE_CMD} {locale}"))
+                    .context("The command was applied, but could not be journaled")?;
+                self.journal_locale = locale;
+            }
+            journal
+                .append(&record)
+                .context("The command was applied, but could not be journaled")?;
+        }
+        Ok(output)
+    }
+}
+
+fn join_units(args: Vec<&str>, at: usize) -> Vec<String> {
+    let is_amount = |arg: &str| {
+        arg.starts_with(|c: char| c.is_ascii_digit())
+            && arg
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    };
+    let is_unit = |arg: &str| arg == "€" || arg.parse::<Currency>().is_ok();
+    let mut joined: Vec<String> = args.iter().copied().map(String::from).collect();
+    if let (Some(first), Some(second)) = (args.get(at), args.get(at + 1))
+        && (is_amount(first) && is_unit(second) || is_unit(first) && is_amount(second))
+    {
+        joined[at] = format!("{first} {second}");
+        joined.remove(at + 1);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(commands: &[&str]) -> Result<String, String> {
+        let mut handler = CommandHandler::new(Model::new());
+        let mut last = None;
+        for command in commands {
+            let result = handler.handle_command(command.to_string());
+            if result.is_error {
+                return Err(format!("{command}: {}", result.command_output));
+            }
+            last = Some(result.command_output);
+        }
+        Ok(last.unwrap_or_default())
+    }
+
+    #[test]
+    fn single_currency_cities_need_no_exchange_rates() {
+        let cheapest = run(&[
+            "add hotel 1 Bern CHF",
+            "add hotel 2 Bern CHF",
+            "add room 1 101 Single 120CHF",
+            "add room 2 201 Single 95CHF",
+            "add charge Bern tax tax all 2.50CHF",
+            "find cheapest Bern Single 2030-01-01 2030-01-03",
+        ]);
+        assert_eq!(cheapest.as_deref(), Ok("00002 201 195.00CHF"));
+    }
+    #[test]
+    fn units_typed_apart_are_joined_to_prices() {
+        let rooms = run(&[
+            "locale de-DE",
+            "add hotel 1 Koeln",
+            "add hotel 2 Bern CHF",
+            "add room 1 101 Single 12,50 €",
+            "add room 1 102 Double 1.200,00 €",
+            "add room 2 201 Single CHF 95",
+            "list rooms",
+        ]);
+        assert_eq!(
+            rooms.as_deref(),
+            Ok("00001 101 Single 12,50 €\n\
+                00001 102 Double 1.200,00 €\n\
+                00002 201 Single 95,00 CHF"),
+        );
+    }
+    #[test]
+    fn only_prices_are_joined() {
+        let hotel = run(&["locale de-DE", "add hotel 3 NYC USD", "show hotel 3"]);
+        assert_eq!(hotel.as_deref(), Ok("00003 NYC USD"));
+    }
+    #[test]
+    fn capacities_are_not_joined_to_prices() {
+        let room = run(&[
+            "locale de-DE",
+            "add hotel 1 Koeln",
+            "add room 1 102 Double 1.200,00 € 3",
+            "update room 1 102 supplement 20 €",
+            "show room 1 102",
+        ]);
+        assert_eq!(
+            room.as_deref(),
+            Ok("00001 102 Double 1.200,00 €\ncapacity 3\nsupplement 20,00 €"),
+        );
+    }
+    #[test]
+    fn default_capacities_follow_the_category() {
+        let suite = run(&[
+            "add hotel 1 Basel",
+            "add room 1 101 Single 100",
+            "add room 1 102 Double 100 3",
+            "update room 1 101 category Suite",
+            "update room 1 102 category Suite",
+            "show room 1 101",
+        ]);
+        assert_eq!(suite.as_deref(), Ok("00001 101 Suite 100.00€\ncapacity 4"));
+        let kept = run(&[
+            "add hotel 1 Basel",
+            "add room 1 102 Double 100 3",
+            "update room 1 102 category Suite",
+            "show room 1 102",
+        ]);
+        assert_eq!(kept.as_deref(), Ok("00001 102 Suite 100.00€\ncapacity 3"));
+        let booked = run(&[
+            "add hotel 1 Basel",
+            "add room 1 101 Suite 100",
+            "book 1 101 2099-01-10 2099-01-12 3 Max Muster",
+            "update room 1 101 category Single",
+        ]);
+        assert!(booked.is_err_and(|err| err.contains("would only sleep 1")));
+    }
+    #[test]
+    fn guest_counts_are_not_joined_to_currencies() {
+        let setup = [
+            "locale de-DE",
+            "set exchange-rates EUR 1 CHF 2",
+            "add hotel 1 Koeln",
+            "add room 1 101 Double 100",
+        ];
+        let found = run(&[
+            &setup[..],
+            &["find available Koeln Double 2030-01-01 2030-01-03 2 CHF"],
+        ]
+        .concat());
+        assert_eq!(found.as_deref(), Ok("00001 101 100,00 CHF"));
+        let quote = run(&[&setup[..], &["quote 1 101 2030-01-01 2030-01-03 2 CHF"]].concat());
+        assert_eq!(
+            quote.as_deref(),
+            Ok("base 100,00 CHF\ntaxes 0,00 CHF\nfees 0,00 CHF\ntotal 100,00 CHF"),
+        );
+    }
+    #[test]
+    fn modified_bookings_keep_their_rate_and_promo() {
+        let modified = run(&[
+            "add hotel 1 Basel",
+            "add room 1 101 Single 100",
+            "add promo SPRING 10% 2030-01-01 2030-02-01 1 1",
+            "book 1 101 2030-01-10 2030-01-12 Max Muster SPRING",
+            "disable promo SPRING",
+            "update room 1 101 price 150",
+            "modify booking 1 1 dates 2030-03-10 2030-03-13",
+        ]);
+        assert_eq!(modified.as_deref(), Ok("1 1 100.00€ 280.00€"));
+    }
+    #[test]
+    fn modified_bookings_only_conflict_with_other_bookings() {
+        let setup = [
+            "add hotel 1 Basel",
+            "add room 1 101 Single 100",
+            "book 1 101 2030-01-10 2030-01-12 Max Muster",
+            "book 1 101 2030-01-14 2030-01-16 Erika Muster",
+        ];
+        let later = run(&[
+            &setup[..],
+            &["modify booking 1 1 dates 2030-01-11 2030-01-14"],
+        ]
+        .concat());
+        assert_eq!(later.as_deref(), Ok("1 1 100.00€ 300.00€"));
+        let overlapping = run(&[
+            &setup[..],
+            &["modify booking 1 1 dates 2030-01-11 2030-01-15"],
+        ]
+        .concat());
+        assert!(overlapping.is_err());
+    }
+    #[test]
+    fn moved_bookings_take_the_rate_of_the_new_room() {
+        let moved = run(&[
+            "add hotel 1 Basel",
+            "add room 1 101 Single 100",
+            "add room 1 102 Single 120",
+            "add promo SPRING 10% 2030-01-01 2030-02-01 1 1",
+            "book 1 101 2030-01-10 2030-01-12 Max Muster SPRING",
+            "disable promo SPRING",
+            "move booking 1 1 102",
+        ]);
+        assert_eq!(moved.as_deref(), Ok("1 1 120.00€ 220.00€"));
     }
 }
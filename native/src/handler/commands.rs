@@ -1,16 +1,64 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeSet, HashMap, VecDeque},
     fmt::{Display, Write},
     ops::Range,
+    path::PathBuf,
+    str::FromStr,
 };
 
 use anyhow::ensure;
 
-use crate::data::{Booking, BookingId, Category, CustomerId, Date, HotelId, Person, Price, RoomId};
+use crate::data::{
+    Amenity, AppliedPromo, Booking, BookingId, Category, Charge, ChargeKind, Currency, Customer,
+    CustomerDetails, CustomerId, CustomerKey, CustomerRef, Date, Discount, HotelData, HotelDetails,
+    HotelId, Locale, Percentage, Person, Price, PricingRule, PromoCode, RateKind, RatePeriod,
+    RemovalPolicy, RoomData, RoomFilter, RoomId, RuleCondition, RuleScope, Stars, Turnover,
+    UsageLimit,
+};
+use crate::model::Quote;
 
 use super::*;
 
-pub fn handle(command: &str, model: &mut Model, args: Vec<&str>) -> Result {
+const TARGET_ROOM: &str = "room";
+const TARGET_HOTEL: &str = "hotel";
+const TARGET_CHEAPEST: &str = "cheapest";
+const TARGET_AVAILABLE: &str = "available";
+const TARGET_BOOKINGS: &str = "bookings";
+const TARGET_ROOMS: &str = "rooms";
+const TARGET_TURNOVER: &str = "turnover";
+const TARGET_BOOKING: &str = "booking";
+const TARGET_RATE: &str = "rate";
+const TARGET_RATES: &str = "rates";
+const TARGET_RULE: &str = "rule";
+const TARGET_RULES: &str = "rules";
+const TARGET_PROMO: &str = "promo";
+const TARGET_PROMOS: &str = "promos";
+const TARGET_CHARGE: &str = "charge";
+const TARGET_CHARGES: &str = "charges";
+const TARGET_EXCHANGE_RATES: &str = "exchange-rates";
+const TARGET_CATEGORY: &str = "category";
+const TARGET_CATEGORIES: &str = "categories";
+const TARGET_AMENITY: &str = "amenity";
+const TARGET_CUSTOMER: &str = "customer";
+const TARGET_CUSTOMER_KEY: &str = "customer-key";
+const TARGET_CUSTOMERS: &str = "customers";
+const TARGET_DUPLICATES: &str = "duplicates";
+const TARGET_DATES: &str = "dates";
+
+/// An amount and unit typed apart only make up a price at this position.
+pub fn price_position(command: &str, args: &[&str]) -> Option<usize> {
+    match (command, *args.first()?) {
+        ("add", TARGET_ROOM) => Some(4),
+        ("add", TARGET_RATE) => Some(6),
+        ("add", TARGET_PROMO) => Some(2),
+        ("add", TARGET_CHARGE) => Some(5),
+        // Only some fields of a room are prices, but no other field takes a unit
+        ("update", TARGET_ROOM) => Some(4),
+        _ => None,
+    }
+}
+
+pub fn handle(command: &str, model: &mut Model, args: Vec<&str>, locale: Locale) -> Result {
     let mut args = VecDeque::from(args);
 
     fn ensure_n_args<'a, const N: usize>(args: &[&'a str]) -> Result<[&'a str; N]> {
@@ -19,11 +67,14 @@ pub fn handle(command: &str, model: &mut Model, args: Vec<&str>) -> Result {
             .map_err(|_| anyhow::format_err!("Expected {N} arguments, got {len}"))
     }
     macro_rules! cmd {
+        ($func:ident ($($extra:expr),*), $($arg:tt)*) => {
+            cmd! { @ $func  [$($arg)*] [$($extra,)*] [] }
+        };
         ($func:ident, $($arg:tt)*) => {
             cmd! { @ $func  [$($arg)*] [] [] }
         };
         ( @ $func:ident [$ty:ty $(, $($rest:tt)*)?] [$($args:expr,)*] [$($pats:pat,)*] ) => {
-            cmd! { @ $func [$($($rest)*)?] [$($args,)* arg.parse::<$ty>()?, ] [$($pats,)* arg, ] }
+            cmd! { @ $func [$($($rest)*)?] [$($args,)* <$ty as Arg>::parse(arg, locale)?, ] [$($pats,)* arg, ] }
         };
         ( @ $func:ident [] [$($args:expr,)*] [$($pats:pat,)*] ) => {{
             let [$($pats,)*] = ensure_n_args(args.as_slices().0)?;
@@ -31,38 +82,318 @@ pub fn handle(command: &str, model: &mut Model, args: Vec<&str>) -> Result {
         }};
     }
 
-    const TARGET_ROOM: &str = "room";
-    const TARGET_HOTEL: &str = "hotel";
-    const TARGET_CHEAPEST: &str = "cheapest";
-    const TARGET_AVAILABLE: &str = "available";
-    const TARGET_BOOKINGS: &str = "bookings";
-    const TARGET_ROOMS: &str = "rooms";
     Ok(match command {
-        "add" => match ensure_target(args.pop_front(), &[TARGET_ROOM, TARGET_HOTEL])? {
-            TARGET_ROOM => cmd!(add_room, _, _, _, _),
-            TARGET_HOTEL => cmd!(add_hotel, _, _),
+        "add" => match ensure_target(
+            args.pop_front(),
+            &[
+                TARGET_ROOM,
+                TARGET_HOTEL,
+                TARGET_RATE,
+                TARGET_RULE,
+                TARGET_PROMO,
+                TARGET_CHARGE,
+                TARGET_CATEGORY,
+                TARGET_AMENITY,
+                TARGET_CUSTOMER,
+            ],
+        )? {
+            TARGET_ROOM => {
+                // Rooms sleep as many guests as their category suggests unless they say otherwise
+                let capacity = take_optional(&mut args, 5)?;
+                let add_room = |model: &mut _, hotel, room, category, price| {
+                    add_room(model, hotel, room, category, price, capacity)
+                };
+                cmd!(add_room, _, _, _, _)
+            }
+            TARGET_HOTEL => {
+                // Hotels charge in euros unless they name another currency
+                let currency = take_optional(&mut args, 3)?.unwrap_or_default();
+                let add_hotel = |model: &mut _, id, city| add_hotel(model, id, city, currency);
+                cmd!(add_hotel, _, _)
+            }
+            TARGET_RATE => cmd!(add_rate, _, _, _, _, _, _),
+            TARGET_RULE => cmd!(add_rule, _, _, _, _, _),
+            TARGET_PROMO => cmd!(add_promo, _, _, _, _, _, _),
+            TARGET_CHARGE => cmd!(add_charge, _, _, _, _, _),
+            TARGET_CATEGORY => cmd!(add_category, _, _),
+            TARGET_AMENITY => cmd!(add_amenity, _, _, _),
+            TARGET_CUSTOMER => {
+                // Details follow the name as `field=value`
+                let details = Vec::from(args.split_off(args.len().min(2)));
+                let add_customer = |model: &mut _, forename, surname| {
+                    add_customer(model, forename, surname, &details)
+                };
+                cmd!(add_customer, _, _)
+            }
+            _ => unreachable!(),
+        },
+        "remove" => {
+            match ensure_target(
+                args.pop_front(),
+                &[
+                    TARGET_ROOM,
+                    TARGET_HOTEL,
+                    TARGET_RATE,
+                    TARGET_RULE,
+                    TARGET_CHARGE,
+                    TARGET_AMENITY,
+                ],
+            )? {
+                TARGET_ROOM => {
+                    let policy = take_flag(&mut args)?.unwrap_or_default();
+                    let remove_room =
+                        |model: &mut _, hotel, id| remove_room(model, hotel, id, policy);
+                    cmd!(remove_room, _, _)
+                }
+                TARGET_HOTEL => {
+                    let policy = take_flag(&mut args)?.unwrap_or_default();
+                    let remove_hotel = |model: &mut _, id| remove_hotel(model, id, policy);
+                    cmd!(remove_hotel, _)
+                }
+                TARGET_RATE => cmd!(remove_rate, _, _, _, _),
+                TARGET_RULE => cmd!(remove_rule, _, _),
+                TARGET_CHARGE => cmd!(remove_charge, _, _),
+                TARGET_AMENITY => cmd!(remove_amenity, _, _, _),
+                _ => unreachable!(),
+            }
+        }
+        "update" => match ensure_target(args.pop_front(), &[TARGET_ROOM])? {
+            TARGET_ROOM => cmd!(update_room(locale), _, _, _, _),
             _ => unreachable!(),
         },
-        "remove" => match ensure_target(args.pop_front(), &[TARGET_ROOM, TARGET_HOTEL])? {
-            TARGET_ROOM => cmd!(remove_room, _, _),
-            TARGET_HOTEL => cmd!(remove_hotel, _),
+        "find" => {
+            let target = ensure_target(args.pop_front(), &[TARGET_CHEAPEST, TARGET_AVAILABLE])?;
+            let (required, excluded) = take_amenities(&mut args, 4)?;
+            let min_stars = take_min_stars(&mut args, 4)?;
+            let filter = RoomFilter {
+                guests: take_guests(&mut args, 4)?,
+                min_stars,
+                required,
+                excluded,
+            };
+            // The currency to report totals in is an optional last argument
+            let currency = take_optional(&mut args, 5)?;
+            let find_cheapest = |model: &mut _, city, category, start, end| {
+                find_cheapest(
+                    model,
+                    city,
+                    category,
+                    Range { start, end },
+                    &filter,
+                    currency,
+                    locale,
+                )
+            };
+            let find_available = |model: &mut _, city, category, start, end| {
+                find_available(
+                    model,
+                    city,
+                    category,
+                    Range { start, end },
+                    &filter,
+                    currency,
+                    locale,
+                )
+            };
+            match target {
+                TARGET_CHEAPEST => cmd!(find_cheapest, _, _, _, _),
+                TARGET_AVAILABLE => cmd!(find_available, _, _, _, _),
+                _ => unreachable!(),
+            }
+        }
+        "list" => match ensure_target(
+            args.pop_front(),
+            &[
+                TARGET_ROOMS,
+                TARGET_BOOKINGS,
+                TARGET_RATES,
+                TARGET_RULES,
+                TARGET_PROMOS,
+                TARGET_CHARGES,
+                TARGET_EXCHANGE_RATES,
+                TARGET_CATEGORIES,
+                TARGET_CUSTOMERS,
+                TARGET_DUPLICATES,
+            ],
+        )? {
+            TARGET_ROOMS => {
+                let label = take_flag(&mut args)?.unwrap_or_default();
+                cmd!(list_rooms(locale, label),)
+            }
+            TARGET_BOOKINGS => match args.front() {
+                Some(&TARGET_CUSTOMER) => {
+                    args.pop_front();
+                    cmd!(list_customer_bookings(locale), _)
+                }
+                _ => cmd!(list_bookings(locale),),
+            },
+            TARGET_RATES => cmd!(list_rates(locale), _, _),
+            TARGET_RULES => cmd!(list_rules, _),
+            TARGET_PROMOS => cmd!(list_promos(locale),),
+            TARGET_CHARGES => cmd!(list_charges(locale), _),
+            TARGET_EXCHANGE_RATES => cmd!(list_exchange_rates,),
+            TARGET_CATEGORIES => cmd!(list_categories,),
+            TARGET_CUSTOMERS => cmd!(list_customers,),
+            TARGET_DUPLICATES => cmd!(list_duplicates,),
             _ => unreachable!(),
         },
-        "find" => match ensure_target(args.pop_front(), &[TARGET_CHEAPEST, TARGET_AVAILABLE])? {
-            TARGET_CHEAPEST => cmd!(find_cheapest, _, _, _, _),
-            TARGET_AVAILABLE => cmd!(find_available, _, _, _, _),
+        "show" => match ensure_target(
+            args.pop_front(),
+            &[TARGET_BOOKING, TARGET_ROOM, TARGET_HOTEL, TARGET_CUSTOMER],
+        )? {
+            TARGET_BOOKING => cmd!(show_booking(locale), _),
+            TARGET_ROOM => cmd!(show_room(locale), _, _),
+            TARGET_HOTEL => cmd!(show_hotel, _),
+            TARGET_CUSTOMER => cmd!(show_customer, _),
             _ => unreachable!(),
         },
-        "list" => match ensure_target(args.pop_front(), &[TARGET_ROOMS, TARGET_BOOKINGS])? {
-            TARGET_ROOMS => cmd!(list_rooms,),
-            TARGET_BOOKINGS => cmd!(list_bookings,),
+        "set" => match ensure_target(
+            args.pop_front(),
+            &[
+                TARGET_TURNOVER,
+                TARGET_HOTEL,
+                TARGET_CUSTOMER,
+                TARGET_CUSTOMER_KEY,
+                TARGET_EXCHANGE_RATES,
+            ],
+        )? {
+            TARGET_TURNOVER => cmd!(set_turnover, _),
+            TARGET_CUSTOMER_KEY => cmd!(set_customer_key, _),
+            TARGET_EXCHANGE_RATES => {
+                // Each currency is followed by its rate
+                let rates = Vec::from(std::mem::take(&mut args));
+                set_exchange_rates(model, &rates)?.to_string()
+            }
+            TARGET_CUSTOMER => {
+                // Addresses may take up the rest of the arguments
+                let value = Vec::from(args.split_off(args.len().min(2))).join(" ");
+                let set_customer =
+                    |model: &mut _, id, field| set_customer(model, id, field, &value);
+                cmd!(set_customer, _, _)
+            }
+            TARGET_HOTEL => {
+                // Names, addresses and contacts may take up the rest of the arguments
+                let value = Vec::from(args.split_off(args.len().min(2))).join(" ");
+                let set_hotel = |model: &mut _, id, field| set_hotel(model, id, field, &value);
+                cmd!(set_hotel, _, _)
+            }
             _ => unreachable!(),
         },
+        "quote" => {
+            let guests = take_guests(&mut args, 4)?;
+            let currency = take_optional(&mut args, 5)?;
+            let quote = |model: &mut _, hotel, room, start, end| {
+                let time = Range { start, end };
+                quote(model, hotel, room, time, guests, currency, locale)
+            };
+            cmd!(quote, _, _, _, _)
+        }
         "cancel" => cmd!(cancel, _, _),
-        "book" => cmd!(book, _, _, _, _, _, _),
+        "modify" => match ensure_target(args.pop_front(), &[TARGET_BOOKING])? {
+            TARGET_BOOKING => {
+                // The dates follow the customer, after the word `dates`
+                ensure_target(args.remove(2), &[TARGET_DATES])?;
+                let modify_booking = |model: &mut _, id, customer, start, end| {
+                    let time = Range { start, end };
+                    modify_booking(model, locale, id, customer, time)
+                };
+                cmd!(modify_booking, _, _, _, _)
+            }
+            _ => unreachable!(),
+        },
+        "move" => match ensure_target(args.pop_front(), &[TARGET_BOOKING])? {
+            TARGET_BOOKING => cmd!(move_booking(locale), _, _, _),
+            _ => unreachable!(),
+        },
+        "book" => {
+            let guests = take_guests(&mut args, 4)?;
+            // Customers are either given by id after the word `customer` or by name
+            let by_id = args.get(4) == Some(&TARGET_CUSTOMER);
+            if by_id {
+                args.remove(4);
+            }
+            // The promo code is an optional last argument
+            let len = if by_id { 6 } else { 7 };
+            let promo = (args.len() == len).then(|| args.pop_back()).flatten();
+            let book = |model: &mut Model, hotel, room, start, end, customer| {
+                let time = Range { start, end };
+                let booking = model.book(hotel, room, time, guests, customer, promo)?;
+                booking_receipt(&booking, locale)
+            };
+            if by_id {
+                let book = |model: &mut _, hotel, room, start, end, id| {
+                    book(model, hotel, room, start, end, CustomerRef::Id(id))
+                };
+                cmd!(book, _, _, _, _, _)
+            } else {
+                let book = |model: &mut _, hotel, room, start, end, forename, surname| {
+                    let person = Person { forename, surname };
+                    book(model, hotel, room, start, end, CustomerRef::Name(person))
+                };
+                cmd!(book, _, _, _, _, _, _)
+            }
+        }
+        "disable" => match ensure_target(args.pop_front(), &[TARGET_PROMO])? {
+            TARGET_PROMO => cmd!(disable_promo, _),
+            _ => unreachable!(),
+        },
+        "import" => match ensure_target(args.pop_front(), &[TARGET_EXCHANGE_RATES])? {
+            TARGET_EXCHANGE_RATES => cmd!(import_exchange_rates, _),
+            _ => unreachable!(),
+        },
+        "merge" => match ensure_target(args.pop_front(), &[TARGET_CUSTOMER])? {
+            TARGET_CUSTOMER => cmd!(merge_customer, _, _),
+            _ => unreachable!(),
+        },
+        "save" => cmd!(save, _),
+        "load" => cmd!(load, _),
         _ => bail!("Unknown command '{command}'"),
     })
 }
+trait Arg: Sized {
+    fn parse(arg: &str, locale: Locale) -> anyhow::Result<Self>;
+}
+macro_rules! plain_args {
+    ($($ty:ty),* $(,)?) => {$(
+        impl Arg for $ty {
+            // Not every error type here is an `anyhow::Error` already
+            #[allow(clippy::needless_question_mark)]
+            fn parse(arg: &str, _: Locale) -> anyhow::Result<Self> {
+                Ok(arg.parse()?)
+            }
+        }
+    )*};
+}
+plain_args!(
+    u32,
+    u64,
+    String,
+    PathBuf,
+    HotelId,
+    Currency,
+    Category,
+    Amenity,
+    Date,
+    RateKind,
+    RuleScope,
+    RuleCondition,
+    Percentage,
+    ChargeKind,
+    UsageLimit,
+    Turnover,
+    CustomerKey,
+);
+impl Arg for Price {
+    fn parse(arg: &str, locale: Locale) -> anyhow::Result<Self> {
+        Price::parse_localized(arg, locale)
+    }
+}
+impl Arg for Discount {
+    fn parse(arg: &str, locale: Locale) -> anyhow::Result<Self> {
+        Discount::parse_localized(arg, locale)
+    }
+}
+
 fn ensure_target<'a>(arg: Option<&'a str>, targets: &[&str]) -> anyhow::Result<&'a str> {
     let target_list = || targets.join(", ");
     let Some(target) = arg else {
@@ -76,8 +407,88 @@ fn ensure_target<'a>(arg: Option<&'a str>, targets: &[&str]) -> anyhow::Result<&
     Ok(target)
 }
 
-fn add_hotel(model: &mut Model, id: HotelId, city: String) -> Result<impl Display> {
-    model.add_hotel(id, city).map(|_| "OK")
+fn take_flag<T>(args: &mut VecDeque<&str>) -> anyhow::Result<Option<T>>
+where
+    T: FromStr<Err = anyhow::Error>,
+{
+    const PREFIX: &str = "--";
+    let mut flags = Vec::new();
+    args.retain(|arg| match arg.strip_prefix(PREFIX) {
+        Some(flag) => {
+            flags.push(flag);
+            false
+        }
+        None => true,
+    });
+    match flags[..] {
+        [] => Ok(None),
+        [flag] => Ok(Some(flag.parse()?)),
+        _ => bail!(
+            "Expected at most one flag, got {PREFIX}{}",
+            flags.join(&format!(" {PREFIX}"))
+        ),
+    }
+}
+
+fn take_optional<T>(args: &mut VecDeque<&str>, len: usize) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    anyhow::Error: From<T::Err>,
+{
+    if args.len() != len {
+        return Ok(None);
+    }
+    Ok(args.pop_back().map(str::parse).transpose()?)
+}
+
+fn take_amenities(
+    args: &mut VecDeque<&str>,
+    from: usize,
+) -> anyhow::Result<(BTreeSet<Amenity>, BTreeSet<Amenity>)> {
+    let mut required = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+    let rest = args.split_off(from.min(args.len()));
+    for arg in rest {
+        if let Some(amenity) = arg.strip_prefix('+') {
+            required.insert(amenity.parse()?);
+        } else if let Some(amenity) = arg.strip_prefix('-') {
+            excluded.insert(amenity.parse()?);
+        } else {
+            args.push_back(arg);
+        }
+    }
+    Ok((required, excluded))
+}
+
+fn take_min_stars(args: &mut VecDeque<&str>, from: usize) -> anyhow::Result<Option<Stars>> {
+    let mut min_stars = None;
+    let rest = args.split_off(from.min(args.len()));
+    for arg in rest {
+        match arg.strip_suffix('*') {
+            Some(stars) => {
+                ensure!(min_stars.is_none(), "Expected at most one star rating");
+                min_stars = Some(stars.parse()?);
+            }
+            None => args.push_back(arg),
+        }
+    }
+    Ok(min_stars)
+}
+
+fn take_guests(args: &mut VecDeque<&str>, at: usize) -> anyhow::Result<u32> {
+    match args.get(at) {
+        Some(arg) if arg.bytes().all(|b| b.is_ascii_digit()) => {
+            let guests = arg.parse()?;
+            args.remove(at);
+            Ok(guests)
+        }
+        _ => Ok(1),
+    }
+}
+
+fn add_hotel(model: &mut Model, id: HotelId, city: String, currency: Currency) -> Result {
+    model.add_hotel(id, city, currency)?;
+    Ok("OK".into())
 }
 fn add_room(
     model: &mut Model,
@@ -85,100 +496,374 @@ fn add_room(
     room: RoomId,
     category: Category,
     price: Price,
+    capacity: Option<u32>,
+) -> Result {
+    model.add_room(hotel, room, category, price, capacity)?;
+    Ok("OK".into())
+}
+fn remove_hotel(model: &mut Model, id: HotelId, policy: RemovalPolicy) -> Result {
+    let cancelled = model.remove_hotel(id, policy)?;
+    removal_report(&cancelled)
+}
+fn remove_room(model: &mut Model, hotel: HotelId, id: RoomId, policy: RemovalPolicy) -> Result {
+    let cancelled = model.remove_room(hotel, id, policy)?;
+    removal_report(&cancelled)
+}
+fn removal_report(cancelled: &[Booking]) -> Result {
+    let mut output = String::from("OK");
+    for Booking { id, customer, .. } in cancelled {
+        write!(output, "\n{id} {customer}")?;
+    }
+    Ok(output)
+}
+fn add_rate(
+    model: &mut Model,
+    hotel: HotelId,
+    room: RoomId,
+    kind: RateKind,
+    start: Date,
+    end: Date,
+    price: Price,
 ) -> Result<impl Display> {
-    model.add_room(hotel, room, category, price).map(|_| "OK")
+    let time = Range { start, end };
+    model
+        .add_rate(hotel, room, RatePeriod { kind, time, price })
+        .map(|_| "OK")
 }
-fn remove_hotel(model: &mut Model, id: HotelId) -> Result<impl Display> {
-    model.remove_hotel(id).map(|_| "OK")
+fn remove_rate(
+    model: &mut Model,
+    hotel: HotelId,
+    room: RoomId,
+    kind: RateKind,
+    start: Date,
+) -> Result<impl Display> {
+    model.remove_rate(hotel, room, kind, &start).map(|_| "OK")
 }
-fn remove_room(model: &mut Model, hotel: HotelId, id: RoomId) -> Result<impl Display> {
-    model.remove_room(hotel, id).map(|_| "OK")
+fn add_rule(
+    model: &mut Model,
+    hotel: HotelId,
+    name: String,
+    scope: RuleScope,
+    condition: RuleCondition,
+    adjustment: Percentage,
+) -> Result<impl Display> {
+    let rule = PricingRule {
+        name,
+        scope,
+        condition,
+        adjustment,
+    };
+    model.add_rule(hotel, rule).map(|_| "OK")
 }
-fn list_rooms(model: &Model) -> Result<impl Display> {
+fn remove_rule(model: &mut Model, hotel: HotelId, name: String) -> Result<impl Display> {
+    model.remove_rule(hotel, &name).map(|_| "OK")
+}
+fn add_charge(
+    model: &mut Model,
+    city: String,
+    name: String,
+    kind: ChargeKind,
+    scope: RuleScope,
+    amount: Price,
+) -> Result<impl Display> {
+    let charge = Charge {
+        name,
+        kind,
+        scope,
+        amount,
+    };
+    model.add_charge(&city, charge).map(|_| "OK")
+}
+fn remove_charge(model: &mut Model, city: String, name: String) -> Result<impl Display> {
+    model.remove_charge(&city, &name).map(|_| "OK")
+}
+const ROOM_PRICE: &str = "price";
+const ROOM_CATEGORY: &str = "category";
+const ROOM_CAPACITY: &str = "capacity";
+const ROOM_SUPPLEMENT: &str = "supplement";
+fn update_room(
+    model: &mut Model,
+    locale: Locale,
+    hotel: HotelId,
+    room: RoomId,
+    field: String,
+    value: String,
+) -> Result<impl Display> {
+    const NO_SUPPLEMENT: &str = "none";
+    match ensure_target(
+        Some(&field),
+        &[ROOM_PRICE, ROOM_CATEGORY, ROOM_CAPACITY, ROOM_SUPPLEMENT],
+    )? {
+        ROOM_PRICE => model.set_room_price(hotel, room, Price::parse(&value, locale)?)?,
+        ROOM_CATEGORY => model.set_room_category(hotel, room, value.parse()?)?,
+        ROOM_CAPACITY => model.set_room_capacity(hotel, room, value.parse()?)?,
+        ROOM_SUPPLEMENT => {
+            let supplement = match value.as_str() {
+                NO_SUPPLEMENT => None,
+                _ => Some(Price::parse(&value, locale)?),
+            };
+            model.set_room_supplement(hotel, room, supplement)?
+        }
+        _ => unreachable!(),
+    }
+    Ok("OK")
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+enum HotelLabel {
+    #[default]
+    Id,
+    Name,
+}
+impl FromStr for HotelLabel {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "ids" => Self::Id,
+            "names" => Self::Name,
+            _ => bail!("Unknown hotel label '{s}'"),
+        })
+    }
+}
+fn list_rooms(model: &Model, locale: Locale, label: HotelLabel) -> Result<impl Display> {
     let mut output = String::new();
-    let mut rooms: Vec<_> = model.rooms().collect();
+    let mut rooms = model.rooms()?;
     rooms.sort_unstable_by_key(|&(hotel, room_id, _)| (hotel, room_id));
+    let names: HashMap<_, _> = match label {
+        HotelLabel::Id => HashMap::new(),
+        HotelLabel::Name => model
+            .hotels()?
+            .into_iter()
+            .filter_map(|(id, hotel)| Some((id, hotel.details.name?)))
+            .collect(),
+    };
     for (hotel, room_id, room_dat) in &rooms {
-        writeln!(
+        let hotel = match names.get(hotel) {
+            Some(name) => name.clone(),
+            None => hotel.to_string(),
+        };
+        write!(
             output,
             "{hotel} {room_id} {cat} {price}",
             cat = room_dat.category,
-            price = room_dat.price,
+            price = room_dat.price.localized(locale),
         )?;
+        for amenity in &room_dat.amenities {
+            write!(output, " {amenity}")?;
+        }
+        writeln!(output)?;
     }
     if output.ends_with("\n") {
         output.pop();
     }
     Ok(output)
 }
-fn list_bookings(model: &Model) -> Result<impl Display> {
+fn show_room(model: &Model, locale: Locale, hotel: HotelId, room: RoomId) -> Result<impl Display> {
+    let RoomData {
+        category,
+        price,
+        capacity,
+        supplement,
+        amenities,
+    } = model.room(hotel, room)?;
+    let mut output = format!("{hotel} {room} {category} {}", price.localized(locale));
+    write!(output, "\n{ROOM_CAPACITY} {capacity}")?;
+    if let Some(supplement) = supplement {
+        write!(
+            output,
+            "\n{ROOM_SUPPLEMENT} {}",
+            supplement.localized(locale)
+        )?;
+    }
+    for amenity in amenities {
+        write!(output, "\n{TARGET_AMENITY} {amenity}")?;
+    }
+    Ok(output)
+}
+fn list_rates(model: &Model, locale: Locale, hotel: HotelId, room: RoomId) -> Result<impl Display> {
     let mut output = String::new();
-    let mut bookings: Vec<_> = model.bookings().collect();
+    for RatePeriod {
+        kind,
+        time: Range { start, end },
+        price,
+    } in &model.rates(hotel, room)?
+    {
+        let price = price.localized(locale);
+        writeln!(output, "{kind} {start} {end} {price}")?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn list_rules(model: &Model, hotel: HotelId) -> Result<impl Display> {
+    let mut output = String::new();
+    for PricingRule {
+        name,
+        scope,
+        condition,
+        adjustment,
+    } in &model.rules(hotel)?
+    {
+        writeln!(output, "{name} {scope} {condition} {adjustment}")?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn list_charges(model: &Model, locale: Locale, city: String) -> Result<impl Display> {
+    let mut output = String::new();
+    for Charge {
+        name,
+        kind,
+        scope,
+        amount,
+    } in &model.charges(&city)?
+    {
+        let amount = amount.localized(locale);
+        writeln!(output, "{name} {kind} {scope} {amount}")?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn list_bookings(model: &Model, locale: Locale) -> Result<impl Display> {
+    let mut bookings = model.bookings()?;
     bookings.sort_unstable_by_key(|booking| booking.id);
+    booking_lines(&bookings, locale)
+}
+fn list_customer_bookings(
+    model: &Model,
+    locale: Locale,
+    customer: CustomerId,
+) -> Result<impl Display> {
+    booking_lines(&model.bookings_of_customer(customer)?, locale)
+}
+fn booking_lines(bookings: &[Booking], locale: Locale) -> Result {
+    let mut output = String::new();
     for Booking {
         time: Range { start, end },
         customer,
         id,
-    } in &bookings
+        guests,
+        rate,
+        total,
+        ..
+    } in bookings
     {
-        writeln!(output, "{id} {customer} {start} {end}")?;
+        let (rate, total) = (rate.localized(locale), total.localized(locale));
+        writeln!(
+            output,
+            "{id} {customer} {start} {end} {guests} {rate} {total}"
+        )?;
     }
     if output.ends_with("\n") {
         output.pop();
     }
     Ok(output)
 }
+/// The cheapest free room, comparing totals in `currency`, in the currency they share or else in
+/// euros.
 fn find_cheapest(
     model: &Model,
     city: String,
     category: Category,
-    start: Date,
-    end: Date,
-) -> Result<impl Display> {
-    let min = model
-        .avaiable(
-            &city,
-            category,
-            Range {
-                start: &start,
-                end: &end,
-            },
-        )?
-        .min_by_key(|(hotel, room, price)| (*price, *hotel, *room));
+    time: Range<Date>,
+    filter: &RoomFilter,
+    currency: Option<Currency>,
+    locale: Locale,
+) -> Result {
+    let available = model.avaiable(&city, category, &time.start..&time.end, filter)?;
+    // Totals that are all in the same currency need no exchange rates to compare
+    let shared = match &available[..] {
+        [(_, _, first), rest @ ..] => rest
+            .iter()
+            .all(|(_, _, quote)| quote.total.currency == first.total.currency)
+            .then_some(first.total.currency),
+        [] => None,
+    };
+    let compared = currency.or(shared).unwrap_or_default();
+    let mut rooms = Vec::new();
+    for (hotel, room, quote) in available {
+        let total = model.exchange(&quote.total, compared)?;
+        rooms.push((total, hotel, room, quote));
+    }
+    let min = rooms
+        .into_iter()
+        .min_by(|(a, h1, r1, _), (b, h2, r2, _)| (a, h1, r1).cmp(&(b, h2, r2)));
 
-    if let Some((hotel, room, price)) = min {
-        let days = (end.into_inner() - start.into_inner()).whole_days();
-        let total = Price {
-            cents: &price.cents * days as u64,
+    if let Some((_, hotel, room, quote)) = min {
+        let quote = match currency {
+            Some(currency) => model.exchange_quote(quote, currency)?,
+            None => quote,
         };
-        Ok(format!("{hotel} {room} {total}"))
+        Ok(format!("{hotel} {room} {}", quote_summary(&quote, locale)))
     } else {
         Ok(String::new())
     }
 }
+fn quote_summary(Quote { total, rules, .. }: &Quote, locale: Locale) -> String {
+    let mut summary = total.localized(locale).to_string();
+    for rule in rules {
+        summary.push(' ');
+        summary.push_str(rule);
+    }
+    summary
+}
+fn quote(
+    model: &Model,
+    hotel: HotelId,
+    room: RoomId,
+    time: Range<Date>,
+    guests: u32,
+    currency: Option<Currency>,
+    locale: Locale,
+) -> Result {
+    let mut quote = model.quote(hotel, room, &time.start..&time.end, guests)?;
+    if let Some(currency) = currency {
+        quote = model.exchange_quote(quote, currency)?;
+    }
+    let Quote {
+        base,
+        rules,
+        taxes,
+        fees,
+        total,
+    } = quote;
+    let mut output = format!("base {}", base.localized(locale));
+    if !rules.is_empty() {
+        write!(output, "\nrules {}", rules.join(" "))?;
+    }
+    write!(
+        output,
+        "\ntaxes {}\nfees {}\ntotal {}",
+        taxes.localized(locale),
+        fees.localized(locale),
+        total.localized(locale),
+    )?;
+    Ok(output)
+}
 fn find_available(
     model: &Model,
     city: String,
     category: Category,
-    start: Date,
-    end: Date,
-) -> Result<impl Display> {
+    time: Range<Date>,
+    filter: &RoomFilter,
+    currency: Option<Currency>,
+    locale: Locale,
+) -> Result {
     let mut output = String::new();
-    let mut rooms: Vec<_> = model
-        .avaiable(
-            &city,
-            category,
-            Range {
-                start: &start,
-                end: &end,
-            },
-        )?
-        .collect();
+    let mut rooms = model.avaiable(&city, category, &time.start..&time.end, filter)?;
 
     rooms.sort_unstable_by_key(|(hotel, room, _)| (*hotel, *room));
 
-    for (hotel, room, price) in &rooms {
-        writeln!(output, "{hotel} {room} {price}")?;
+    for (hotel, room, quote) in rooms {
+        let quote = match currency {
+            Some(currency) => model.exchange_quote(quote, currency)?,
+            None => quote,
+        };
+        writeln!(output, "{hotel} {room} {}", quote_summary(&quote, locale))?;
     }
 
     if output.ends_with("\n") {
@@ -186,20 +871,375 @@ fn find_available(
     }
     Ok(output)
 }
-fn book(
+fn booking_receipt(booking: &Booking, locale: Locale) -> Result {
+    let Booking {
+        id,
+        customer,
+        rate,
+        total,
+        ..
+    } = booking;
+    Ok(format!(
+        "{id} {customer} {} {}",
+        rate.localized(locale),
+        total.localized(locale),
+    ))
+}
+fn add_promo(
     model: &mut Model,
-    hotel_id: HotelId,
-    room_id: RoomId,
+    code: String,
+    discount: Discount,
     start: Date,
     end: Date,
-    forename: String,
-    surname: String,
+    limit: UsageLimit,
+    customer_limit: UsageLimit,
 ) -> Result<impl Display> {
-    let customer = model.get_customer(Person { forename, surname });
-    let id = model.book(hotel_id, room_id, Range { start, end }, customer)?;
-    Ok(format!("{id} {customer}"))
+    let promo = PromoCode {
+        code,
+        discount,
+        valid: Range { start, end },
+        limit,
+        customer_limit,
+        active: true,
+    };
+    model.add_promo(promo).map(|_| "OK")
+}
+fn disable_promo(model: &mut Model, code: String) -> Result<impl Display> {
+    model.disable_promo(&code).map(|_| "OK")
+}
+fn list_promos(model: &Model, locale: Locale) -> Result<impl Display> {
+    let mut output = String::new();
+    for (
+        PromoCode {
+            code,
+            discount,
+            valid: Range { start, end },
+            limit,
+            customer_limit,
+            active,
+        },
+        uses,
+    ) in &model.promos()?
+    {
+        let state = if *active { "active" } else { "disabled" };
+        let discount = match discount {
+            Discount::Percent(_) => discount.to_string(),
+            Discount::Fixed(price) => price.localized(locale).to_string(),
+        };
+        writeln!(
+            output,
+            "{code} {discount} {start} {end} {uses} {limit} {customer_limit} {state}"
+        )?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn set_turnover(model: &mut Model, turnover: Turnover) -> Result<impl Display> {
+    model.set_turnover(turnover).map(|_| "OK")
+}
+fn set_customer_key(model: &mut Model, key: CustomerKey) -> Result<impl Display> {
+    model.set_customer_key(key).map(|_| "OK")
+}
+const CUSTOMER_EMAIL: &str = "email";
+const CUSTOMER_PHONE: &str = "phone";
+const CUSTOMER_BIRTH_DATE: &str = "birth-date";
+const CUSTOMER_ADDRESS: &str = "address";
+const CUSTOMER_FIELDS: &[&str] = &[
+    CUSTOMER_EMAIL,
+    CUSTOMER_PHONE,
+    CUSTOMER_BIRTH_DATE,
+    CUSTOMER_ADDRESS,
+];
+fn set_customer_detail(
+    details: &mut CustomerDetails,
+    field: &str,
+    value: Option<&str>,
+) -> anyhow::Result<()> {
+    match ensure_target(Some(field), CUSTOMER_FIELDS)? {
+        CUSTOMER_EMAIL => {
+            if let Some(email) = value {
+                ensure!(email.contains('@'), "Invalid email address '{email}'");
+            }
+            details.email = value.map(Into::into);
+        }
+        CUSTOMER_PHONE => details.phone = value.map(Into::into),
+        CUSTOMER_BIRTH_DATE => details.birth_date = value.map(str::parse).transpose()?,
+        CUSTOMER_ADDRESS => details.address = value.map(Into::into),
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+fn add_customer(model: &mut Model, forename: String, surname: String, args: &[&str]) -> Result {
+    let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+    for &arg in args {
+        match arg.split_once('=') {
+            Some((field, value)) if CUSTOMER_FIELDS.contains(&field) => {
+                ensure!(
+                    fields.iter().all(|&(other, _)| other != field),
+                    "Customer {field} given twice",
+                );
+                fields.push((field, vec![value]));
+            }
+            _ => match fields.last_mut() {
+                Some((_, value)) => value.push(arg),
+                None => bail!("Expected customer details like {CUSTOMER_EMAIL}=..., got '{arg}'"),
+            },
+        }
+    }
+    let mut details = CustomerDetails::default();
+    for (field, value) in fields {
+        let value = value.join(" ");
+        ensure!(!value.is_empty(), "Missing value for {field}");
+        set_customer_detail(&mut details, field, Some(&value))?;
+    }
+    let person = Person { forename, surname };
+    let id = model.add_customer(Customer { person, details })?;
+    Ok(id.to_string())
+}
+fn set_customer(model: &mut Model, id: CustomerId, field: String, value: &str) -> Result {
+    const NO_VALUE: &str = "none";
+    ensure!(!value.is_empty(), "Missing value for {field}");
+    let mut details = model.customer(id)?.details;
+    set_customer_detail(&mut details, &field, (value != NO_VALUE).then_some(value))?;
+    model.set_customer_details(id, details)?;
+    Ok("OK".into())
+}
+fn list_customers(model: &Model) -> Result<impl Display> {
+    let mut output = String::new();
+    for (id, Customer { person, .. }) in &model.customers()? {
+        writeln!(output, "{id} {} {}", person.forename, person.surname)?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn list_duplicates(model: &Model) -> Result<impl Display> {
+    let mut output = String::new();
+    for (a, b, distance) in model.likely_duplicates()? {
+        let (a_person, b_person) = (model.customer(a)?.person, model.customer(b)?.person);
+        writeln!(
+            output,
+            "{a} {b} {distance} {} {} / {} {}",
+            a_person.forename, a_person.surname, b_person.forename, b_person.surname,
+        )?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn merge_customer(model: &mut Model, from: CustomerId, into: CustomerId) -> Result<impl Display> {
+    model.merge_customers(from, into)?;
+    Ok("OK")
+}
+fn show_customer(model: &Model, id: CustomerId) -> Result<impl Display> {
+    let Customer {
+        person: Person { forename, surname },
+        details:
+            CustomerDetails {
+                email,
+                phone,
+                birth_date,
+                address,
+            },
+    } = model.customer(id)?;
+    let mut output = format!("{id} {forename} {surname}");
+    let details = [
+        (CUSTOMER_EMAIL, email),
+        (CUSTOMER_PHONE, phone),
+        (CUSTOMER_BIRTH_DATE, birth_date.map(|date| date.to_string())),
+        (CUSTOMER_ADDRESS, address),
+    ];
+    for (field, value) in details {
+        if let Some(value) = value {
+            write!(output, "\n{field} {value}")?;
+        }
+    }
+    Ok(output)
+}
+const HOTEL_NAME: &str = "name";
+const HOTEL_ADDRESS: &str = "address";
+const HOTEL_STARS: &str = "stars";
+const HOTEL_CHECK_IN: &str = "check-in";
+const HOTEL_CHECK_OUT: &str = "check-out";
+const HOTEL_CONTACT: &str = "contact";
+fn set_hotel(model: &mut Model, id: HotelId, field: String, value: &str) -> Result {
+    const NO_VALUE: &str = "none";
+    ensure!(!value.is_empty(), "Missing value for {field}");
+    let value = (value != NO_VALUE).then_some(value);
+    let mut details = model.hotel(id)?.details;
+    match ensure_target(
+        Some(&field),
+        &[
+            HOTEL_NAME,
+            HOTEL_ADDRESS,
+            HOTEL_STARS,
+            HOTEL_CHECK_IN,
+            HOTEL_CHECK_OUT,
+            HOTEL_CONTACT,
+        ],
+    )? {
+        HOTEL_NAME => details.name = value.map(Into::into),
+        HOTEL_ADDRESS => details.address = value.map(Into::into),
+        HOTEL_STARS => details.stars = value.map(str::parse).transpose()?,
+        HOTEL_CHECK_IN => details.check_in = value.map(str::parse).transpose()?,
+        HOTEL_CHECK_OUT => details.check_out = value.map(str::parse).transpose()?,
+        HOTEL_CONTACT => details.contact = value.map(Into::into),
+        _ => unreachable!(),
+    }
+    model.set_hotel_details(id, details)?;
+    Ok("OK".into())
+}
+fn show_hotel(model: &Model, id: HotelId) -> Result<impl Display> {
+    let HotelData {
+        city,
+        currency,
+        details:
+            HotelDetails {
+                name,
+                address,
+                stars,
+                check_in,
+                check_out,
+                contact,
+            },
+    } = model.hotel(id)?;
+    let mut output = format!("{id} {city} {currency}");
+    let details = [
+        (HOTEL_NAME, name),
+        (HOTEL_ADDRESS, address),
+        (HOTEL_STARS, stars.map(|stars| stars.to_string())),
+        (HOTEL_CHECK_IN, check_in.map(|time| time.to_string())),
+        (HOTEL_CHECK_OUT, check_out.map(|time| time.to_string())),
+        (HOTEL_CONTACT, contact),
+    ];
+    for (field, value) in details {
+        if let Some(value) = value {
+            write!(output, "\n{field} {value}")?;
+        }
+    }
+    Ok(output)
+}
+fn show_booking(model: &Model, locale: Locale, id: BookingId) -> Result<impl Display> {
+    let Booking {
+        hotel,
+        room,
+        time: Range { start, end },
+        customer,
+        guests,
+        rate,
+        total,
+        promo,
+        ..
+    } = model.booking(id)?;
+    let (rate, total) = (rate.localized(locale), total.localized(locale));
+    let mut output =
+        format!("{id} {hotel} {room} {start} {end} {guests} {customer} {rate} {total}");
+    if let Some(AppliedPromo { code, discount }) = promo {
+        write!(output, " {code} {}", discount.localized(locale))?;
+    }
+    Ok(output)
 }
 fn cancel(model: &mut Model, booking: BookingId, customer: CustomerId) -> Result<impl Display> {
     model.cancel(booking, customer)?;
     Ok("OK")
 }
+fn modify_booking(
+    model: &mut Model,
+    locale: Locale,
+    id: BookingId,
+    customer: CustomerId,
+    time: Range<Date>,
+) -> Result {
+    let booking = model.modify_booking(id, customer, time)?;
+    booking_receipt(&booking, locale)
+}
+fn move_booking(
+    model: &mut Model,
+    locale: Locale,
+    id: BookingId,
+    hotel: HotelId,
+    room: u64,
+) -> Result {
+    let booking = model.move_booking(id, hotel, room)?;
+    booking_receipt(&booking, locale)
+}
+fn import_exchange_rates(model: &mut Model, path: PathBuf) -> Result<impl Display> {
+    model.import_exchange_rates(&path)?;
+    Ok("OK")
+}
+fn set_exchange_rates(model: &mut Model, rates: &[&str]) -> Result<impl Display> {
+    ensure!(
+        rates.len().is_multiple_of(2),
+        "Expected a rate after every currency"
+    );
+    let rates = rates
+        .chunks_exact(2)
+        .map(|pair| Ok((pair[0].parse()?, pair[1].parse()?)))
+        .collect::<Result<_>>()?;
+    model.set_exchange_rates(rates)?;
+    Ok("OK")
+}
+/// The command that sets the exchange rates that `model` has now, which is journaled in place of
+/// an import so that replaying it does not read the file again. It reads the same in every
+/// locale.
+pub fn set_exchange_rates_command(model: &Model) -> Result {
+    let mut command = String::from("set exchange-rates");
+    for (currency, rate) in model.exchange_rates()? {
+        write!(command, " {currency} {rate}")?;
+    }
+    Ok(command)
+}
+fn list_exchange_rates(model: &Model) -> Result<impl Display> {
+    let mut output = String::new();
+    for (currency, rate) in &model.exchange_rates()? {
+        writeln!(output, "{currency} {rate}")?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn add_category(model: &mut Model, category: Category, capacity: u32) -> Result<impl Display> {
+    model.add_category(category, capacity)?;
+    Ok("OK")
+}
+fn add_amenity(
+    model: &mut Model,
+    hotel: HotelId,
+    room: RoomId,
+    amenity: Amenity,
+) -> Result<impl Display> {
+    model.add_amenity(hotel, room, amenity)?;
+    Ok("OK")
+}
+fn remove_amenity(
+    model: &mut Model,
+    hotel: HotelId,
+    room: RoomId,
+    amenity: Amenity,
+) -> Result<impl Display> {
+    model.remove_amenity(hotel, room, &amenity)?;
+    Ok("OK")
+}
+fn list_categories(model: &Model) -> Result<impl Display> {
+    let mut output = String::new();
+    for (category, capacity) in &model.categories()? {
+        writeln!(output, "{category} {capacity}")?;
+    }
+    if output.ends_with("\n") {
+        output.pop();
+    }
+    Ok(output)
+}
+fn save(model: &Model, path: PathBuf) -> Result<impl Display> {
+    model.save(&path)?;
+    Ok("OK")
+}
+fn load(model: &mut Model, path: PathBuf) -> Result<impl Display> {
+    model.load(&path)?;
+    Ok("OK")
+}
@@ -4,7 +4,10 @@ use jni::{
     sys::jlong,
 };
 
-use crate::handler::CommandHandler;
+use crate::{
+    handler::CommandHandler,
+    model::{Model, storage::SqliteStorage},
+};
 
 #[repr(C)]
 union StatePointer {
@@ -22,13 +25,42 @@ impl StatePointer {
 }
 const _: () = assert!(size_of::<StatePointer>() == size_of::<jlong>());
 
+const JOURNAL_VAR: &str = "A4NATIVE_JOURNAL";
+const DATABASE_VAR: &str = "A4NATIVE_DATABASE";
+
+fn init_handler() -> anyhow::Result<CommandHandler> {
+    let journal = std::env::var_os(JOURNAL_VAR);
+    let model = match std::env::var_os(DATABASE_VAR) {
+        Some(path) => {
+            // Replaying a journal on top of a database that already contains its changes
+            // would apply them twice
+            anyhow::ensure!(
+                journal.is_none(),
+                "{JOURNAL_VAR} and {DATABASE_VAR} cannot be used together",
+            );
+            Model::with_storage(Box::new(SqliteStorage::open(path.as_ref())?))
+        }
+        None => Model::new(),
+    };
+    Ok(match journal {
+        Some(path) => CommandHandler::with_journal(model, path.as_ref())?,
+        None => CommandHandler::new(model),
+    })
+}
+
 #[unsafe(no_mangle)]
 extern "system" fn Java_edu_kit_kastel_CommandHandler_initNative(
-    _env: JNIEnv,
+    mut env: JNIEnv,
     _class: JClass,
 ) -> StatePointer {
-    let boxed_state = Box::new(CommandHandler::new(crate::model::Model::new()));
-    StatePointer::new(Box::into_raw(boxed_state))
+    match init_handler() {
+        Ok(handler) => StatePointer::new(Box::into_raw(Box::new(handler))),
+        Err(err) => {
+            env.throw_new("java/lang/IllegalStateException", format!("{err:#}"))
+                .unwrap();
+            StatePointer::new(std::ptr::null_mut())
+        }
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -0,0 +1,134 @@
+//! Append-only journal of the commands that changed the model.
+//!
+//! Every record is a single command line terminated by `\n`. A record without its terminator
+//! can only be the result of a crash during an append, so it is discarded when the journal is
+//! opened.
+//!
+//! A journal can be started over from a checkpoint, a snapshot of the whole model kept in a
+//! numbered file next to it. The journal then only holds a record naming the checkpoint,
+//! followed by the commands since. Replacing the journal is what switches to the new
+//! checkpoint, so a crash in between leaves the old checkpoint and journal in place.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, ensure};
+
+const TERMINATOR: u8 = b'\n';
+const CHECKPOINT: &str = "checkpoint";
+
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    checkpoint: u64,
+}
+pub struct Contents {
+    pub checkpoint: Option<PathBuf>,
+    pub records: Vec<String>,
+}
+impl Journal {
+    pub fn open(path: &Path) -> Result<(Self, Contents)> {
+        let mut file = Self::open_file(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        let complete_len = contents
+            .iter()
+            .rposition(|&byte| byte == TERMINATOR)
+            .map_or(0, |idx| idx + 1);
+        if complete_len < contents.len() {
+            file.set_len(complete_len as u64)?;
+            file.sync_data()?;
+            contents.truncate(complete_len);
+        }
+
+        let mut records: Vec<String> = String::from_utf8(contents)
+            .with_context(|| format!("Journal {} is corrupted", path.display()))?
+            .lines()
+            .map(String::from)
+            .collect();
+        let checkpoint = match records.first().and_then(|record| {
+            record
+                .strip_prefix(CHECKPOINT)
+                .and_then(|number| number.strip_prefix(' '))
+        }) {
+            Some(number) => {
+                let number = number
+                    .parse()
+                    .with_context(|| format!("Journal {} is corrupted", path.display()))?;
+                records.remove(0);
+                number
+            }
+            None => 0,
+        };
+        let journal = Self {
+            path: path.into(),
+            file,
+            checkpoint,
+        };
+        let contents = Contents {
+            checkpoint: (checkpoint != 0).then(|| journal.checkpoint_path(checkpoint)),
+            records,
+        };
+        Ok((journal, contents))
+    }
+    fn open_file(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("Cannot open journal {}", path.display()))
+    }
+    fn checkpoint_path(&self, number: u64) -> PathBuf {
+        let mut path = self.path.clone();
+        path.as_mut_os_string()
+            .push(format!(".{CHECKPOINT}-{number}"));
+        path
+    }
+
+    pub fn reset(&mut self, save: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+        let number = self.checkpoint + 1;
+        save(&self.checkpoint_path(number))?;
+        let mut tmp = self.path.clone();
+        tmp.as_mut_os_string().push(".tmp");
+        let mut file = File::create(&tmp)
+            .with_context(|| format!("Cannot create journal {}", tmp.display()))?;
+        file.write_all(format!("{CHECKPOINT} {number}").as_bytes())?;
+        file.write_all(&[TERMINATOR])?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Cannot replace journal {}", self.path.display()))?;
+        self.file = Self::open_file(&self.path)?;
+        let old = std::mem::replace(&mut self.checkpoint, number);
+        if old != 0 {
+            // Nothing refers to the old checkpoint any more, so failing to remove it is harmless
+            let _ = fs::remove_file(self.checkpoint_path(old));
+        }
+        Ok(())
+    }
+
+    pub fn append(&mut self, record: &str) -> Result<()> {
+        ensure!(
+            !record.as_bytes().contains(&TERMINATOR),
+            "Journal records must be single lines",
+        );
+        let mut line = Vec::with_capacity(record.len() + 1);
+        line.extend_from_slice(record.as_bytes());
+        line.push(TERMINATOR);
+        let len = self.file.metadata()?.len();
+        if let Err(err) = self
+            .file
+            .write_all(&line)
+            .and_then(|_| self.file.sync_data())
+        {
+            // Do not leave a partial record behind for the next append to run into
+            let _ = self.file.set_len(len);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 mod data;
 mod handler;
 mod java_glue;
+mod journal;
 mod model;
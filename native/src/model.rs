@@ -1,38 +1,92 @@
-use std::{
-    collections::{HashMap, hash_map::Entry},
-    ops::Range,
-};
+mod category;
+mod customer;
+mod exchange;
+mod pricing;
+mod promo;
+mod snapshot;
+pub mod storage;
+
+use std::ops::Range;
 
 use anyhow::{Result, bail, ensure};
 
 use crate::data::{
-    Booking, BookingId, Category, CustomerId, Date, HotelData, HotelId, Person, Price, RoomData,
+    Amenity, AppliedPromo, Booking, BookingId, Category, Currency, Customer, CustomerDetails,
+    CustomerId, CustomerRef, Date, HotelData, HotelDetails, HotelId, Price, RemovalPolicy,
+    RoomData, RoomFilter, Turnover,
 };
+pub use pricing::Quote;
+use storage::{MemoryStorage, Storage};
 
 pub struct Model {
-    cur_booking_id: u64,
-    cur_customer_id: u64,
-    hotels: HashMap<HotelId, HotelData>,
-    customers: HashMap<Person, CustomerId>,
+    storage: Box<dyn Storage>,
 }
+
+const TURNOVER_SETTING: &str = "turnover";
+
 impl Model {
     pub fn new() -> Self {
-        Self {
-            cur_booking_id: 1,
-            cur_customer_id: 1,
-            hotels: Default::default(),
-            customers: Default::default(),
+        Self::with_storage(Box::new(MemoryStorage::default()))
+    }
+    pub fn with_storage(storage: Box<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    fn atomically<T>(&mut self, change: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.storage.begin()?;
+        match change(self) {
+            Ok(value) => {
+                self.storage.commit()?;
+                Ok(value)
+            }
+            Err(err) => {
+                self.storage.rollback()?;
+                Err(err)
+            }
         }
     }
 
-    pub fn add_hotel(&mut self, id: HotelId, city: String) -> Result<()> {
-        match self.hotels.entry(id) {
-            Entry::Occupied(_) => bail!("Hotel ID is already in use"),
-            Entry::Vacant(vacant) => vacant.insert(HotelData {
-                city,
-                rooms: Default::default(),
-            }),
+    pub fn turnover(&self) -> Result<Turnover> {
+        turnover(&*self.storage)
+    }
+    pub fn set_turnover(&mut self, turnover: Turnover) -> Result<()> {
+        self.storage
+            .set_setting(TURNOVER_SETTING, &turnover.to_string())
+    }
+
+    pub fn add_hotel(&mut self, id: HotelId, city: String, currency: Currency) -> Result<()> {
+        if self.storage.hotel(id)?.is_some() {
+            bail!("Hotel ID is already in use")
+        }
+        let hotel = HotelData {
+            city,
+            currency,
+            details: HotelDetails::default(),
         };
+        self.storage.insert_hotel(id, hotel)
+    }
+    pub fn hotel(&self, id: HotelId) -> Result<HotelData> {
+        self.storage
+            .hotel(id)?
+            .ok_or_else(|| anyhow::format_err!("Unknown hotel ID {id}"))
+    }
+    pub fn hotels(&self) -> Result<Vec<(HotelId, HotelData)>> {
+        self.storage.hotels()
+    }
+    pub fn set_hotel_details(&mut self, id: HotelId, details: HotelDetails) -> Result<()> {
+        let hotel = self.hotel(id)?;
+        self.storage
+            .update_hotel(id, HotelData { details, ..hotel })
+    }
+    fn ensure_hotel_currency(&self, hotel_id: HotelId, price: &Price) -> Result<()> {
+        let Some(hotel) = self.storage.hotel(hotel_id)? else {
+            bail!("Hotel with id {hotel_id} does not exist")
+        };
+        ensure!(
+            price.currency == hotel.currency,
+            "Prices of hotel {hotel_id} must be in {}",
+            hotel.currency,
+        );
         Ok(())
     }
     pub fn add_room(
@@ -41,127 +95,415 @@ impl Model {
         room_id: u64,
         category: Category,
         price: Price,
+        capacity: Option<u32>,
     ) -> Result<()> {
-        let Some(hotel_data) = self.hotels.get_mut(&hotel_id) else {
+        if self.storage.hotel(hotel_id)?.is_none() {
             bail!("Hotel with id {hotel_id} does not exist")
+        }
+        if self.storage.room(hotel_id, room_id)?.is_some() {
+            bail!("Hotel ID is already in use")
+        }
+        self.ensure_hotel_currency(hotel_id, &price)?;
+        let default_capacity = self.category_capacity(&category)?;
+        let capacity = capacity.unwrap_or(default_capacity);
+        ensure!(capacity > 0, "Rooms must sleep at least one guest");
+        let room = RoomData {
+            category,
+            price,
+            capacity,
+            supplement: None,
+            amenities: Default::default(),
         };
-        match hotel_data.rooms.entry(room_id) {
-            Entry::Occupied(_) => bail!("Hotel ID is already in use"),
-            Entry::Vacant(vacant) => vacant.insert(RoomData {
-                category,
-                price,
-                bookings: Default::default(),
-            }),
+        self.storage.insert_room(hotel_id, room_id, room)
+    }
+    /// Existing bookings keep the rate they were booked at.
+    pub fn set_room_price(&mut self, hotel_id: HotelId, room_id: u64, price: Price) -> Result<()> {
+        let room = self.room(hotel_id, room_id)?;
+        self.ensure_hotel_currency(hotel_id, &price)?;
+        self.storage
+            .update_room(hotel_id, room_id, RoomData { price, ..room })
+    }
+    pub fn set_room_capacity(
+        &mut self,
+        hotel_id: HotelId,
+        room_id: u64,
+        capacity: u32,
+    ) -> Result<()> {
+        let room = self.room(hotel_id, room_id)?;
+        ensure!(capacity > 0, "Rooms must sleep at least one guest");
+        self.ensure_bookings_fit(hotel_id, room_id, capacity)?;
+        self.storage
+            .update_room(hotel_id, room_id, RoomData { capacity, ..room })
+    }
+    pub fn set_room_supplement(
+        &mut self,
+        hotel_id: HotelId,
+        room_id: u64,
+        supplement: Option<Price>,
+    ) -> Result<()> {
+        let room = self.room(hotel_id, room_id)?;
+        if let Some(supplement) = &supplement {
+            self.ensure_hotel_currency(hotel_id, supplement)?;
+        }
+        self.storage
+            .update_room(hotel_id, room_id, RoomData { supplement, ..room })
+    }
+    pub fn set_room_category(
+        &mut self,
+        hotel_id: HotelId,
+        room_id: u64,
+        category: Category,
+    ) -> Result<()> {
+        let room = self.room(hotel_id, room_id)?;
+        let default_capacity = self.category_capacity(&category)?;
+        // Rooms that sleep as many guests as their category suggests keep doing so
+        let capacity = if room.capacity == self.category_capacity(&room.category)? {
+            default_capacity
+        } else {
+            room.capacity
         };
-        Ok(())
+        self.ensure_bookings_fit(hotel_id, room_id, capacity)?;
+        let room = RoomData {
+            category,
+            capacity,
+            ..room
+        };
+        self.storage.update_room(hotel_id, room_id, room)
     }
-    pub fn remove_room(&mut self, hotel_id: HotelId, room_id: u64) -> Result<()> {
-        self.hotels
-            .get_mut(&hotel_id)
-            .ok_or_else(|| anyhow::format_err!("Unknown hotel ID {hotel_id}"))?
-            .rooms
-            .remove_entry(&room_id)
-            .ok_or_else(|| anyhow::format_err!("Unknown room ID {room_id}"))?;
+    fn ensure_bookings_fit(&self, hotel_id: HotelId, room_id: u64, capacity: u32) -> Result<()> {
+        let today = Date::today();
+        let too_large = self
+            .storage
+            .bookings_of(hotel_id, Some(room_id))?
+            .into_iter()
+            .filter(|booking| booking.time.end > today && booking.guests > capacity)
+            .min_by_key(|booking| booking.id);
+        if let Some(booking) = too_large {
+            bail!(
+                "Booking {} is for {} guests, but the room would only sleep {capacity}",
+                booking.id,
+                booking.guests,
+            )
+        }
         Ok(())
     }
-    pub fn remove_hotel(&mut self, id: HotelId) -> Result<()> {
-        self.hotels
-            .remove_entry(&id)
-            .ok_or_else(|| anyhow::format_err!("Unknown hotel ID {id}"))?;
+    pub fn add_amenity(&mut self, hotel_id: HotelId, room_id: u64, amenity: Amenity) -> Result<()> {
+        let mut room = self.room(hotel_id, room_id)?;
+        ensure!(
+            !room.amenities.contains(&amenity),
+            "Room {room_id} already has {amenity}",
+        );
+        room.amenities.insert(amenity);
+        self.storage.update_room(hotel_id, room_id, room)
+    }
+    pub fn remove_amenity(
+        &mut self,
+        hotel_id: HotelId,
+        room_id: u64,
+        amenity: &Amenity,
+    ) -> Result<()> {
+        let mut room = self.room(hotel_id, room_id)?;
+        ensure!(
+            room.amenities.remove(amenity),
+            "Room {room_id} has no {amenity}"
+        );
+        self.storage.update_room(hotel_id, room_id, room)
+    }
+    pub fn remove_room(
+        &mut self,
+        hotel_id: HotelId,
+        room_id: u64,
+        policy: RemovalPolicy,
+    ) -> Result<Vec<Booking>> {
+        if self.storage.hotel(hotel_id)?.is_none() {
+            bail!("Unknown hotel ID {hotel_id}")
+        }
+        if self.storage.room(hotel_id, room_id)?.is_none() {
+            bail!("Unknown room ID {room_id}")
+        }
+        let future = self.future_bookings(hotel_id, Some(room_id), policy)?;
+        self.atomically(|model| {
+            model.cancel_all(&future)?;
+            model.storage.remove_room(hotel_id, room_id)?;
+            Ok(future)
+        })
+    }
+    pub fn remove_hotel(&mut self, id: HotelId, policy: RemovalPolicy) -> Result<Vec<Booking>> {
+        if self.storage.hotel(id)?.is_none() {
+            bail!("Unknown hotel ID {id}")
+        }
+        let future = self.future_bookings(id, None, policy)?;
+        self.atomically(|model| {
+            model.cancel_all(&future)?;
+            model.storage.remove_hotel(id)?;
+            Ok(future)
+        })
+    }
+    fn future_bookings(
+        &self,
+        hotel_id: HotelId,
+        room_id: Option<u64>,
+        policy: RemovalPolicy,
+    ) -> Result<Vec<Booking>> {
+        if policy == RemovalPolicy::Force {
+            return Ok(Vec::new());
+        }
+        let today = Date::today();
+        let mut future = self.storage.bookings_of(hotel_id, room_id)?;
+        future.retain(|booking| booking.time.end > today);
+        future.sort_unstable_by_key(|booking| booking.id);
+        if policy == RemovalPolicy::Refuse && !future.is_empty() {
+            let ids: Vec<_> = future
+                .iter()
+                .map(|booking| booking.id.to_string())
+                .collect();
+            bail!("There are future bookings: {}", ids.join(", "))
+        }
+        Ok(future)
+    }
+    fn cancel_all(&mut self, bookings: &[Booking]) -> Result<()> {
+        for booking in bookings {
+            self.storage.remove_booking(booking.id)?;
+        }
         Ok(())
     }
-    pub fn rooms(&self) -> impl Iterator<Item = (HotelId, u64, &RoomData)> {
-        self.hotels
-            .iter()
-            .flat_map(|(hi, hd)| hd.rooms.iter().map(|(ri, rd)| (*hi, *ri, rd)))
+    pub fn rooms(&self) -> Result<Vec<(HotelId, u64, RoomData)>> {
+        self.storage.rooms()
     }
     pub fn avaiable(
         &self,
         city: &str,
         category: Category,
         time: Range<&Date>,
-    ) -> Result<impl Iterator<Item = (HotelId, u64, &Price)>> {
+        filter: &RoomFilter,
+    ) -> Result<Vec<(HotelId, u64, Quote)>> {
         if time.start >= time.end {
             bail!("Invalid date range")
         }
-        Ok(self
-            .hotels
-            .iter()
-            .filter(move |(_, hd)| hd.city == city)
-            .flat_map(move |(hi, hd)| {
-                let time = time.clone();
-                hd.rooms.iter().filter_map(move |(ri, rd)| {
-                    if rd.category != category {
-                        return None;
-                    }
-                    if rd.is_occupied(time.clone()) {
-                        return None;
-                    }
-                    Some((*hi, *ri, &rd.price))
-                })
-            }))
+        ensure!(filter.guests > 0, "A stay needs at least one guest");
+        if let Some(amenity) = filter.required.intersection(&filter.excluded).next() {
+            bail!("{amenity} cannot be both required and excluded")
+        }
+        self.category_capacity(&category)?;
+        let turnover = self.turnover()?;
+        let mut available = Vec::new();
+        for (hotel, room, data) in self.storage.rooms_in_city(city)? {
+            if data.category != category || !filter.accepts(&data) {
+                continue;
+            }
+            if filter.min_stars.is_some() && !filter.accepts_hotel(&self.hotel(hotel)?) {
+                continue;
+            }
+            if self
+                .storage
+                .is_occupied(hotel, room, time.clone(), turnover)?
+            {
+                continue;
+            }
+            let quote = self.quote_room(hotel, room, &data, time.clone(), filter.guests)?;
+            available.push((hotel, room, quote));
+        }
+        Ok(available)
     }
+    /// A customer booking by name is only added if the booking succeeds.
     pub fn book(
         &mut self,
         hotel_id: HotelId,
         room_id: u64,
         time: Range<Date>,
-        customer: CustomerId,
-    ) -> Result<BookingId> {
+        guests: u32,
+        customer: CustomerRef,
+        promo: Option<&str>,
+    ) -> Result<Booking> {
         if time.start >= time.end {
             bail!("Invalid date range")
         }
-        let room = self
-            .hotels
-            .get_mut(&hotel_id)
-            .ok_or_else(|| anyhow::format_err!("Unknown hotel ID {hotel_id}"))?
-            .rooms
-            .get_mut(&room_id)
-            .ok_or_else(|| anyhow::format_err!("Unknown room number {hotel_id}"))?;
-        if room.is_occupied({
+        if self.storage.hotel(hotel_id)?.is_none() {
+            bail!("Unknown hotel ID {hotel_id}")
+        }
+        let Some(room) = self.storage.room(hotel_id, room_id)? else {
+            bail!("Unknown room number {room_id}")
+        };
+        let turnover = self.turnover()?;
+        let stay = {
             let Range { start, end } = &time;
             Range { start, end }
-        }) {
+        };
+        if self
+            .storage
+            .is_occupied(hotel_id, room_id, stay.clone(), turnover)?
+        {
             bail!("Room is already occupied during that time frame");
         }
-        let id = self.cur_booking_id;
-        self.cur_booking_id += 1;
-        room.bookings.push(Booking { time, customer, id });
-        Ok(id)
-    }
-
-    pub fn get_customer(&mut self, person: Person) -> CustomerId {
-        *self.customers.entry(person).or_insert_with(|| {
-            let id = self.cur_customer_id;
-            self.cur_customer_id += 1;
-            id
+        let quote = self.quote_room(hotel_id, room_id, &room, stay, guests)?;
+        let existing = self.find_customer(&customer)?;
+        // A new customer gets the next id, under which there are no bookings yet
+        let customer_id = match existing {
+            Some(id) => id,
+            None => self.storage.counters()?.customer,
+        };
+        // Discounts only apply to the room, not to taxes and fees
+        let promo = match promo {
+            Some(code) => Some(self.redeem(code, customer_id, &time.start, &quote.base)?),
+            None => None,
+        };
+        let total = discounted(quote.total, promo.as_ref())?;
+        self.atomically(|model| {
+            let customer = match (existing, customer) {
+                (Some(id), _) => id,
+                (None, CustomerRef::Name(person)) => model.insert_customer(Customer {
+                    person,
+                    details: CustomerDetails::default(),
+                })?,
+                // Customers given by id always exist
+                (None, CustomerRef::Id(_)) => unreachable!(),
+            };
+            let mut counters = model.storage.counters()?;
+            let id = counters.booking;
+            counters.booking += 1;
+            model.storage.set_counters(counters)?;
+            let booking = Booking {
+                hotel: hotel_id,
+                room: room_id,
+                time,
+                customer,
+                id,
+                guests,
+                rate: room.price,
+                total,
+                promo,
+            };
+            model.storage.insert_booking(booking.clone())?;
+            Ok(booking)
         })
     }
 
     pub fn cancel(&mut self, booking_id: BookingId, customer: CustomerId) -> Result<()> {
-        for hotel in self.hotels.values_mut() {
-            for room in hotel.rooms.values_mut() {
-                let Some(idx) = room
-                    .bookings
-                    .iter()
-                    .position(|booking| booking.id == booking_id)
-                else {
-                    continue;
-                };
-                ensure!(
-                    room.bookings[idx].customer == customer,
-                    "This booking does not belong to customer {customer}"
-                );
-                room.bookings.swap_remove(idx);
-                return Ok(());
-            }
+        let booking = self.booking(booking_id)?;
+        ensure!(
+            booking.customer == customer,
+            "This booking does not belong to customer {customer}"
+        );
+        self.storage.remove_booking(booking_id)?;
+        Ok(())
+    }
+    pub fn modify_booking(
+        &mut self,
+        booking_id: BookingId,
+        customer: CustomerId,
+        time: Range<Date>,
+    ) -> Result<Booking> {
+        let booking = self.booking(booking_id)?;
+        ensure!(
+            booking.customer == customer,
+            "This booking does not belong to customer {customer}"
+        );
+        self.rebook(Booking { time, ..booking })
+    }
+    /// Unlike changing the dates, this takes no customer, as it is the hotel that moves guests
+    /// between rooms.
+    pub fn move_booking(
+        &mut self,
+        booking_id: BookingId,
+        hotel_id: HotelId,
+        room_id: u64,
+    ) -> Result<Booking> {
+        let booking = self.booking(booking_id)?;
+        if self.storage.hotel(hotel_id)?.is_none() {
+            bail!("Unknown hotel ID {hotel_id}")
+        }
+        let Some(room) = self.storage.room(hotel_id, room_id)? else {
+            bail!("Unknown room number {room_id}")
+        };
+        self.rebook(Booking {
+            hotel: hotel_id,
+            room: room_id,
+            rate: room.price,
+            ..booking
+        })
+    }
+    /// Prices the stay again at the rate of the booking. It keeps the discount it was booked with,
+    /// up to the new price of the room.
+    fn rebook(&mut self, booking: Booking) -> Result<Booking> {
+        if booking.time.start >= booking.time.end {
+            bail!("Invalid date range")
+        }
+        if self.storage.hotel(booking.hotel)?.is_none() {
+            bail!("Unknown hotel ID {}", booking.hotel)
+        }
+        let Some(room) = self.storage.room(booking.hotel, booking.room)? else {
+            bail!("Unknown room number {}", booking.room)
+        };
+        let turnover = self.turnover()?;
+        let stay = {
+            let Range { start, end } = &booking.time;
+            Range { start, end }
+        };
+        if self.storage.is_occupied_except(
+            booking.hotel,
+            booking.room,
+            stay.clone(),
+            turnover,
+            booking.id,
+        )? {
+            bail!("Room is already occupied during that time frame");
         }
-        bail!("Could not find booking with id {booking_id}")
+        // Seasonal rates and pricing rules still apply to the new nights
+        let room = RoomData {
+            price: booking.rate.clone(),
+            ..room
+        };
+        let quote = self.quote_room(booking.hotel, booking.room, &room, stay, booking.guests)?;
+        // The promo code is not redeemed again, so it may have been disabled or used up since
+        let promo = match booking.promo {
+            Some(AppliedPromo { code, discount }) => Some(AppliedPromo {
+                code,
+                discount: self
+                    .exchange(&discount, quote.base.currency)?
+                    .min(quote.base.clone()),
+            }),
+            None => None,
+        };
+        let booking = Booking {
+            total: discounted(quote.total, promo.as_ref())?,
+            promo,
+            ..booking
+        };
+        self.atomically(|model| {
+            model.storage.remove_booking(booking.id)?;
+            model.storage.insert_booking(booking.clone())?;
+            Ok(booking)
+        })
+    }
+
+    pub fn booking(&self, id: BookingId) -> Result<Booking> {
+        self.storage
+            .booking(id)?
+            .ok_or_else(|| anyhow::format_err!("Could not find booking with id {id}"))
+    }
+    pub fn room(&self, hotel_id: HotelId, room_id: u64) -> Result<RoomData> {
+        self.storage
+            .room(hotel_id, room_id)?
+            .ok_or_else(|| anyhow::format_err!("Unknown room ID {room_id}"))
     }
+    pub fn bookings(&self) -> Result<Vec<Booking>> {
+        self.storage.bookings()
+    }
+}
+
+fn turnover(storage: &dyn Storage) -> Result<Turnover> {
+    match storage.setting(TURNOVER_SETTING)? {
+        Some(turnover) => turnover.parse(),
+        None => Ok(Turnover::default()),
+    }
+}
 
-    pub fn bookings(&self) -> impl Iterator<Item = &Booking> {
-        self.hotels
-            .values()
-            .flat_map(|hotel| hotel.rooms.values().flat_map(|room| room.bookings.iter()))
+/// Discounts only apply to the room and not to taxes and fees.
+fn discounted(total: Price, promo: Option<&AppliedPromo>) -> Result<Price> {
+    match promo {
+        Some(AppliedPromo { code, discount }) => total
+            .checked_sub(discount)
+            .ok_or_else(|| anyhow::format_err!("Promo code {code} takes off more than the total")),
+        None => Ok(total),
     }
 }
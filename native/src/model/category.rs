@@ -0,0 +1,52 @@
+use anyhow::{Result, bail, ensure};
+
+use super::{Model, storage::Storage};
+use crate::data::{Category, RuleScope};
+
+impl Model {
+    pub fn add_category(&mut self, category: Category, capacity: u32) -> Result<()> {
+        ensure_new(&*self.storage, &category, capacity)?;
+        self.storage.insert_category(category, capacity)
+    }
+    pub fn categories(&self) -> Result<Vec<(Category, u32)>> {
+        let mut added = self.storage.categories()?;
+        added.sort_unstable();
+        Ok(Category::builtin().chain(added).collect())
+    }
+    pub fn category_capacity(&self, category: &Category) -> Result<u32> {
+        capacity(&*self.storage, category)
+    }
+    pub(super) fn ensure_scope(&self, scope: &RuleScope) -> Result<()> {
+        ensure_scope(&*self.storage, scope)
+    }
+}
+
+pub(super) fn ensure_new(storage: &dyn Storage, category: &Category, capacity: u32) -> Result<()> {
+    ensure!(capacity > 0, "Rooms must sleep at least one guest");
+    // Categories that only differ in case would be too easy to mix up
+    let name = category.as_str().to_lowercase();
+    if let Some((known, _)) = Category::builtin()
+        .chain(storage.categories()?)
+        .find(|(known, _)| known.as_str().to_lowercase() == name)
+    {
+        bail!("Category {known} already exists")
+    }
+    Ok(())
+}
+pub(super) fn capacity(storage: &dyn Storage, category: &Category) -> Result<u32> {
+    if let Some(capacity) = category.builtin_capacity() {
+        return Ok(capacity);
+    }
+    storage
+        .categories()?
+        .into_iter()
+        .find(|(known, _)| known == category)
+        .map(|(_, capacity)| capacity)
+        .ok_or_else(|| anyhow::format_err!("Unknown category '{category}'"))
+}
+pub(super) fn ensure_scope(storage: &dyn Storage, scope: &RuleScope) -> Result<()> {
+    if let RuleScope::Category(category) = scope {
+        capacity(storage, category)?;
+    }
+    Ok(())
+}
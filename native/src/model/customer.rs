@@ -0,0 +1,312 @@
+//! A new customer is taken for an existing one if they share the detail named by the customer key
+//! setting. Bookings made with a name alone go to the only customer with that name, or to a new
+//! customer once the booking succeeds.
+
+use anyhow::{Result, bail, ensure};
+
+use super::Model;
+use crate::data::{
+    self, Booking, Customer, CustomerDetails, CustomerId, CustomerKey, CustomerRef, Person,
+};
+
+const CUSTOMER_KEY_SETTING: &str = "customer-key";
+
+impl Model {
+    pub fn customer_key(&self) -> Result<CustomerKey> {
+        match self.storage.setting(CUSTOMER_KEY_SETTING)? {
+            Some(key) => key.parse(),
+            None => Ok(CustomerKey::default()),
+        }
+    }
+    pub fn set_customer_key(&mut self, key: CustomerKey) -> Result<()> {
+        self.storage
+            .set_setting(CUSTOMER_KEY_SETTING, &key.to_string())
+    }
+
+    /// Adds a customer and returns their id, or the id of the existing customer that the
+    /// customer key takes them for.
+    pub fn add_customer(&mut self, customer: Customer) -> Result<CustomerId> {
+        let key = self.customer_key()?;
+        let mut matches = self
+            .storage
+            .customers()?
+            .into_iter()
+            .filter(|(_, other)| key.matches(&customer, other))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        matches.sort_unstable();
+        match matches[..] {
+            [] => self.atomically(|model| model.insert_customer(customer)),
+            [id] => Ok(id),
+            _ => bail!(
+                "Customers {} share the {key} of the new customer",
+                ids(&matches)
+            ),
+        }
+    }
+    pub fn customers(&self) -> Result<Vec<(CustomerId, Customer)>> {
+        let mut customers = self.storage.customers()?;
+        customers.sort_unstable_by_key(|&(id, _)| id);
+        Ok(customers)
+    }
+    pub fn bookings_of_customer(&self, id: CustomerId) -> Result<Vec<Booking>> {
+        self.customer(id)?;
+        let mut bookings = self.storage.bookings_of_customer(id)?;
+        bookings.sort_unstable_by_key(|booking| booking.id);
+        Ok(bookings)
+    }
+    pub fn customer(&self, id: CustomerId) -> Result<Customer> {
+        self.storage
+            .customer(id)?
+            .ok_or_else(|| anyhow::format_err!("Unknown customer ID {id}"))
+    }
+    pub fn set_customer_details(&mut self, id: CustomerId, details: CustomerDetails) -> Result<()> {
+        let customer = self.customer(id)?;
+        self.storage.update_customer(
+            id,
+            Customer {
+                details,
+                ..customer
+            },
+        )
+    }
+
+    pub fn merge_customers(&mut self, from: CustomerId, into: CustomerId) -> Result<()> {
+        ensure!(from != into, "Cannot merge customer {from} into itself");
+        let merged = self.customer(from)?;
+        let customer = self.customer(into)?;
+        let bookings = self.storage.bookings_of_customer(from)?;
+        let details = CustomerDetails {
+            email: customer.details.email.or(merged.details.email),
+            phone: customer.details.phone.or(merged.details.phone),
+            birth_date: customer.details.birth_date.or(merged.details.birth_date),
+            address: customer.details.address.or(merged.details.address),
+        };
+        self.atomically(|model| {
+            for booking in bookings {
+                model.storage.remove_booking(booking.id)?;
+                model.storage.insert_booking(Booking {
+                    customer: into,
+                    ..booking
+                })?;
+            }
+            model.storage.remove_customer(from)?;
+            model.storage.update_customer(
+                into,
+                Customer {
+                    details,
+                    ..customer
+                },
+            )
+        })
+    }
+    /// Pairs sorted by id, with the number of letters that tell the names apart.
+    pub fn likely_duplicates(&self) -> Result<Vec<(CustomerId, CustomerId, usize)>> {
+        let customers: Vec<_> = self
+            .customers()?
+            .into_iter()
+            .map(|(id, customer)| (id, fold_name(&customer.person)))
+            .collect();
+        let mut duplicates = Vec::new();
+        for (idx, (a, a_name)) in customers.iter().enumerate() {
+            for (b, b_name) in &customers[idx + 1..] {
+                let distance = edit_distance(a_name, b_name);
+                let longest = a_name.chars().count().max(b_name.chars().count());
+                if distance * LETTERS_PER_TYPO <= longest {
+                    duplicates.push((*a, *b, distance));
+                }
+            }
+        }
+        Ok(duplicates)
+    }
+
+    pub(super) fn find_customer(&self, customer: &CustomerRef) -> Result<Option<CustomerId>> {
+        match customer {
+            CustomerRef::Id(id) => {
+                self.customer(*id)?;
+                Ok(Some(*id))
+            }
+            CustomerRef::Name(person) => {
+                let mut matches = self
+                    .storage
+                    .customers()?
+                    .into_iter()
+                    .filter(|(_, customer)| customer.person == *person)
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>();
+                matches.sort_unstable();
+                match matches[..] {
+                    [] => Ok(None),
+                    [id] => Ok(Some(id)),
+                    _ => bail!(
+                        "Customers {} are all named {} {}, book with a customer ID instead",
+                        ids(&matches),
+                        person.forename,
+                        person.surname,
+                    ),
+                }
+            }
+        }
+    }
+    pub(super) fn insert_customer(&mut self, customer: Customer) -> Result<CustomerId> {
+        let mut counters = self.storage.counters()?;
+        let id = counters.customer;
+        counters.customer += 1;
+        self.storage.set_counters(counters)?;
+        self.storage.insert_customer(id, customer)?;
+        Ok(id)
+    }
+}
+
+/// How many letters of a name may hide a typo for two names to count as alike.
+const LETTERS_PER_TYPO: usize = 6;
+
+fn fold_name(Person { forename, surname }: &Person) -> String {
+    let mut folded = String::new();
+    // Case folding already spells ß as ss
+    for ch in data::fold(&format!("{forename} {surname}")).chars() {
+        match ch {
+            'ä' => folded.push_str("ae"),
+            'ö' => folded.push_str("oe"),
+            'ü' => folded.push_str("ue"),
+            _ => folded.push(ch),
+        }
+    }
+    folded
+}
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the start of `a` so far to every start of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b_ch) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(a_ch != b_ch);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn ids(ids: &[CustomerId]) -> String {
+    let ids: Vec<_> = ids.iter().map(CustomerId::to_string).collect();
+    ids.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Currency, Date, HotelId};
+
+    fn customer(forename: &str, surname: &str, email: Option<&str>) -> Customer {
+        Customer {
+            person: Person {
+                forename: forename.into(),
+                surname: surname.into(),
+            },
+            details: CustomerDetails {
+                email: email.map(Into::into),
+                ..CustomerDetails::default()
+            },
+        }
+    }
+    // A single room, booked for a week by each customer in turn
+    fn with_bookings(customers: &[Customer]) -> Model {
+        let mut model = Model::new();
+        let hotel = HotelId::try_from(1).unwrap();
+        model
+            .add_hotel(hotel, "Basel".into(), Currency::EUR)
+            .unwrap();
+        let price = "100".parse().unwrap();
+        model
+            .add_room(hotel, 101, "Single".parse().unwrap(), price, None)
+            .unwrap();
+        let start: Date = "2030-01-01".parse().unwrap();
+        let end: Date = "2031-01-01".parse().unwrap();
+        let days: Vec<_> = start.nights_until(&end).collect();
+        for (week, customer) in days.chunks(7).zip(customers) {
+            let id = model.add_customer(customer.clone()).unwrap();
+            let time = week[0].clone()..week[1].clone();
+            model
+                .book(hotel, 101, time, 1, CustomerRef::Id(id), None)
+                .unwrap();
+        }
+        model
+    }
+
+    #[test]
+    fn customers_are_matched_on_the_customer_key() {
+        let mut model = Model::new();
+        let max = customer("Max", "Muster", Some("max@example.org"));
+        let renamed = customer("Maximilian", "Muster", Some("MAX@example.org"));
+        assert_eq!(model.add_customer(max.clone()).unwrap(), 1);
+        assert_eq!(model.add_customer(renamed.clone()).unwrap(), 1);
+        model.set_customer_key(CustomerKey::Name).unwrap();
+        assert_eq!(model.add_customer(renamed).unwrap(), 2);
+        assert_eq!(model.add_customer(max.clone()).unwrap(), 1);
+        model.set_customer_key(CustomerKey::None).unwrap();
+        assert_eq!(model.add_customer(max.clone()).unwrap(), 3);
+        // Names alone no longer tell the customers apart
+        assert!(model.find_customer(&CustomerRef::Name(max.person)).is_err());
+    }
+
+    #[test]
+    fn merging_moves_bookings_and_fills_in_details() {
+        let mut model = with_bookings(&[
+            customer("Max", "Müller", None),
+            customer("Max", "Mueller", Some("max@example.org")),
+            customer("Erika", "Muster", Some("erika@example.org")),
+        ]);
+        model
+            .set_customer_details(
+                1,
+                CustomerDetails {
+                    email: Some("mm@example.org".into()),
+                    phone: Some("+41 61 000 00 00".into()),
+                    ..CustomerDetails::default()
+                },
+            )
+            .unwrap();
+        model.merge_customers(1, 2).unwrap();
+        let ids = |model: &Model, customer| {
+            model
+                .bookings_of_customer(customer)
+                .unwrap()
+                .into_iter()
+                .map(|booking| booking.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&model, 2), [1, 2]);
+        assert_eq!(ids(&model, 3), [3]);
+        assert!(model.customer(1).is_err());
+        let merged = model.customer(2).unwrap();
+        assert_eq!(merged.person.surname, "Mueller");
+        assert_eq!(merged.details.email.as_deref(), Some("max@example.org"));
+        assert_eq!(merged.details.phone.as_deref(), Some("+41 61 000 00 00"));
+        assert!(model.merge_customers(2, 2).is_err());
+        assert!(model.merge_customers(1, 2).is_err());
+    }
+
+    #[test]
+    fn duplicates_allow_umlauts_and_a_typo_every_few_letters() {
+        let mut model = Model::new();
+        for (forename, surname) in [
+            ("Max", "Müller"),
+            ("MAX", "MUELLER"),
+            ("Max", "Mueler"),
+            ("Erika", "Muster"),
+            ("Al", "Bo"),
+            ("Al", "Be"),
+        ] {
+            model
+                .add_customer(customer(forename, surname, None))
+                .unwrap();
+        }
+        assert_eq!(
+            model.likely_duplicates().unwrap(),
+            [(1, 2, 0), (1, 3, 1), (2, 3, 1)]
+        );
+    }
+}
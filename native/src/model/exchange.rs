@@ -0,0 +1,81 @@
+//! Exchange rates are loaded from a local file rather than fetched, so that converting a price
+//! gives the same result until the table is replaced. Each line holds a currency code and the value
+//! of one unit of it in a common reference unit, e.g. `CHF 1.0638`.
+
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use super::{Model, Quote};
+use crate::data::{Currency, ExchangeRate, Price};
+
+const COMMENT: char = '#';
+
+impl Model {
+    pub fn import_exchange_rates(&mut self, path: &Path) -> Result<()> {
+        let table = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read exchange rates {}", path.display()))?;
+        let mut rates = Vec::new();
+        for (idx, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(COMMENT) {
+                continue;
+            }
+            let parse = || -> Result<(Currency, ExchangeRate)> {
+                let mut fields = line.split_whitespace();
+                let (Some(currency), Some(rate), None) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    bail!("Expected a currency code and a rate")
+                };
+                Ok((currency.parse()?, rate.parse()?))
+            };
+            rates.push(
+                parse().with_context(|| format!("Invalid exchange rate on line {}", idx + 1))?,
+            );
+        }
+        self.set_exchange_rates(rates)
+    }
+    pub fn set_exchange_rates(&mut self, rates: Vec<(Currency, ExchangeRate)>) -> Result<()> {
+        let mut seen = HashSet::new();
+        for (currency, _) in &rates {
+            ensure!(
+                seen.insert(currency),
+                "Duplicate exchange rate for {currency}"
+            );
+        }
+        self.storage.set_exchange_rates(rates)
+    }
+    pub fn exchange_rates(&self) -> Result<Vec<(Currency, ExchangeRate)>> {
+        let mut rates = self.storage.exchange_rates()?;
+        rates.sort_unstable_by_key(|&(currency, _)| currency);
+        Ok(rates)
+    }
+
+    pub fn exchange(&self, price: &Price, currency: Currency) -> Result<Price> {
+        if price.currency == currency {
+            return Ok(price.clone());
+        }
+        let from = self.exchange_rate(price.currency)?;
+        let to = self.exchange_rate(currency)?;
+        Ok(price.exchange(&from, currency, &to))
+    }
+    /// The total stays the sum of its parts.
+    pub fn exchange_quote(&self, quote: Quote, currency: Currency) -> Result<Quote> {
+        let base = self.exchange(&quote.base, currency)?;
+        let taxes = self.exchange(&quote.taxes, currency)?;
+        let fees = self.exchange(&quote.fees, currency)?;
+        Ok(Quote {
+            total: base.clone() + &taxes + &fees,
+            base,
+            taxes,
+            fees,
+            ..quote
+        })
+    }
+    fn exchange_rate(&self, currency: Currency) -> Result<ExchangeRate> {
+        self.storage
+            .exchange_rate(currency)?
+            .ok_or_else(|| anyhow::format_err!("No exchange rate for {currency}"))
+    }
+}
@@ -0,0 +1,238 @@
+//! Each night of a stay costs the price of the event period covering it, else the price of the
+//! season covering it, else the room's own price. The pricing rules of the hotel then adjust the
+//! nights they match, each relative to those nightly rates. Taxes and fees of the hotel's city come
+//! on top.
+
+use std::ops::Range;
+
+use anyhow::{Result, bail, ensure};
+
+use super::Model;
+use crate::data::{
+    Charge, ChargeKind, Date, HotelId, Price, PricingRule, RateKind, RatePeriod, RoomData, RoomId,
+    RuleCondition,
+};
+
+pub struct Quote {
+    pub base: Price,
+    pub rules: Vec<String>,
+    pub taxes: Price,
+    pub fees: Price,
+    pub total: Price,
+}
+
+impl Model {
+    pub fn add_rate(&mut self, hotel_id: HotelId, room_id: RoomId, rate: RatePeriod) -> Result<()> {
+        self.room(hotel_id, room_id)?;
+        ensure!(rate.time.start < rate.time.end, "Invalid date range");
+        self.ensure_hotel_currency(hotel_id, &rate.price)?;
+        for other in self.storage.rates(hotel_id, room_id)? {
+            if other.kind.is_season() != rate.kind.is_season() {
+                continue;
+            }
+            if other.time.start < rate.time.end && rate.time.start < other.time.end {
+                bail!(
+                    "Rate period overlaps the {} period from {} to {}",
+                    other.kind,
+                    other.time.start,
+                    other.time.end,
+                )
+            }
+        }
+        self.storage.insert_rate(hotel_id, room_id, rate)
+    }
+    pub fn rates(&self, hotel_id: HotelId, room_id: RoomId) -> Result<Vec<RatePeriod>> {
+        self.room(hotel_id, room_id)?;
+        let mut rates = self.storage.rates(hotel_id, room_id)?;
+        rates.sort_unstable_by(|a, b| (&a.time.start, a.kind).cmp(&(&b.time.start, b.kind)));
+        Ok(rates)
+    }
+    pub fn remove_rate(
+        &mut self,
+        hotel_id: HotelId,
+        room_id: RoomId,
+        kind: RateKind,
+        start: &Date,
+    ) -> Result<()> {
+        self.room(hotel_id, room_id)?;
+        ensure!(
+            self.storage.remove_rate(hotel_id, room_id, kind, start)?,
+            "No {kind} rate period starts on {start}",
+        );
+        Ok(())
+    }
+
+    pub fn add_rule(&mut self, hotel_id: HotelId, rule: PricingRule) -> Result<()> {
+        let rules = self.rules(hotel_id)?;
+        ensure!(
+            rules.iter().all(|other| other.name != rule.name),
+            "Hotel {hotel_id} already has a rule named {}",
+            rule.name,
+        );
+        self.ensure_scope(&rule.scope)?;
+        self.storage.insert_rule(hotel_id, rule)
+    }
+    pub fn rules(&self, hotel_id: HotelId) -> Result<Vec<PricingRule>> {
+        if self.storage.hotel(hotel_id)?.is_none() {
+            bail!("Unknown hotel ID {hotel_id}")
+        }
+        let mut rules = self.storage.rules(hotel_id)?;
+        rules.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(rules)
+    }
+    pub fn remove_rule(&mut self, hotel_id: HotelId, name: &str) -> Result<()> {
+        if self.storage.hotel(hotel_id)?.is_none() {
+            bail!("Unknown hotel ID {hotel_id}")
+        }
+        ensure!(
+            self.storage.remove_rule(hotel_id, name)?,
+            "Hotel {hotel_id} has no rule named {name}",
+        );
+        Ok(())
+    }
+
+    pub fn add_charge(&mut self, city: &str, charge: Charge) -> Result<()> {
+        ensure!(
+            self.storage
+                .charges(city)?
+                .iter()
+                .all(|other| other.name != charge.name),
+            "{city} already has a charge named {}",
+            charge.name,
+        );
+        self.ensure_scope(&charge.scope)?;
+        self.storage.insert_charge(city, charge)
+    }
+    pub fn charges(&self, city: &str) -> Result<Vec<Charge>> {
+        let mut charges = self.storage.charges(city)?;
+        charges.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(charges)
+    }
+    pub fn remove_charge(&mut self, city: &str, name: &str) -> Result<()> {
+        ensure!(
+            self.storage.remove_charge(city, name)?,
+            "{city} has no charge named {name}",
+        );
+        Ok(())
+    }
+
+    /// Whether or not the room is free.
+    pub fn quote(
+        &self,
+        hotel_id: HotelId,
+        room_id: RoomId,
+        time: Range<&Date>,
+        guests: u32,
+    ) -> Result<Quote> {
+        if time.start >= time.end {
+            bail!("Invalid date range")
+        }
+        let room = self.room(hotel_id, room_id)?;
+        self.quote_room(hotel_id, room_id, &room, time, guests)
+    }
+    pub(super) fn quote_room(
+        &self,
+        hotel_id: HotelId,
+        room_id: RoomId,
+        room: &RoomData,
+        time: Range<&Date>,
+        guests: u32,
+    ) -> Result<Quote> {
+        let Some(hotel) = self.storage.hotel(hotel_id)? else {
+            bail!("Unknown hotel ID {hotel_id}")
+        };
+        ensure!(guests > 0, "A stay needs at least one guest");
+        ensure!(
+            guests <= room.capacity,
+            "Room {room_id} sleeps at most {} guests",
+            room.capacity,
+        );
+        let currency = hotel.currency;
+        // Every guest after the first costs the supplement on top of each night
+        let supplement = match &room.supplement {
+            Some(supplement) => supplement * (guests - 1) as usize,
+            None => Price::zero(currency),
+        };
+        let rates = self.storage.rates(hotel_id, room_id)?;
+        let nights: Vec<_> = time
+            .start
+            .nights_until(time.end)
+            .map(|night| {
+                let rate = nightly_rate(&rates, &room.price, &night).clone() + &supplement;
+                (night, rate)
+            })
+            .collect();
+
+        let mut total = Price::sum(currency, nights.iter().map(|(_, rate)| rate));
+        let mut discounts = Price::zero(currency);
+        let mut applied = Vec::new();
+        for PricingRule {
+            name,
+            scope,
+            condition,
+            adjustment,
+        } in self.rules(hotel_id)?
+        {
+            if !scope.includes(&room.category) {
+                continue;
+            }
+            let matched = Price::sum(
+                currency,
+                nights
+                    .iter()
+                    .filter(|(night, _)| match condition {
+                        RuleCondition::Weekend => night.is_weekend_night(),
+                        RuleCondition::MinNights(min) => nights.len() >= min as usize,
+                    })
+                    .map(|(_, rate)| rate),
+            );
+            if matched.is_zero() {
+                continue;
+            }
+            let percent = adjustment.get();
+            let change = matched.percent(percent.unsigned_abs());
+            if percent < 0 {
+                discounts += &change;
+            } else {
+                total += &change;
+            }
+            applied.push(name);
+        }
+        // Discounts are capped at 100% each, but several of them can still add up to more
+        let base = total
+            .checked_sub(&discounts)
+            .unwrap_or_else(|| Price::zero(currency));
+
+        let mut taxes = Price::zero(currency);
+        let mut fees = Price::zero(currency);
+        for charge in self.storage.charges(&hotel.city)? {
+            if !charge.scope.includes(&room.category) {
+                continue;
+            }
+            // Charges are set per city, so they need not be in the currency of the hotel
+            let amount = self.exchange(&charge.amount, currency)?;
+            match charge.kind {
+                ChargeKind::Tax => taxes += &(&amount * (nights.len() * guests as usize)),
+                ChargeKind::Fee => fees += &amount,
+            }
+        }
+        Ok(Quote {
+            total: base.clone() + &taxes + &fees,
+            base,
+            rules: applied,
+            taxes,
+            fees,
+        })
+    }
+}
+
+fn nightly_rate<'a>(rates: &'a [RatePeriod], base: &'a Price, night: &Date) -> &'a Price {
+    let covering = |season| {
+        rates
+            .iter()
+            .find(|rate| rate.kind.is_season() == season && rate.time.contains(night))
+    };
+    covering(false)
+        .or_else(|| covering(true))
+        .map_or(base, |rate| &rate.price)
+}
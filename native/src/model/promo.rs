@@ -0,0 +1,80 @@
+//! A code counts as used by every booking that was made with it, so cancelling a booking frees its
+//! use again. Codes are valid for arrival dates rather than booking dates, which keeps replaying
+//! the journal independent of the day it happens on.
+
+use anyhow::{Result, bail, ensure};
+
+use super::Model;
+use crate::data::{AppliedPromo, CustomerId, Date, Discount, Price, PromoCode};
+
+impl Model {
+    pub fn add_promo(&mut self, promo: PromoCode) -> Result<()> {
+        ensure!(promo.valid.start < promo.valid.end, "Invalid date range");
+        if self.storage.promo(&promo.code)?.is_some() {
+            bail!("Promo code {} already exists", promo.code)
+        }
+        self.storage.insert_promo(promo)
+    }
+    pub fn promos(&self) -> Result<Vec<(PromoCode, usize)>> {
+        let mut promos = self.storage.promos()?;
+        promos.sort_unstable_by(|a, b| a.code.cmp(&b.code));
+        promos
+            .into_iter()
+            .map(|promo| {
+                let uses = self.storage.bookings_with_promo(&promo.code)?.len();
+                Ok((promo, uses))
+            })
+            .collect()
+    }
+    pub fn disable_promo(&mut self, code: &str) -> Result<()> {
+        let promo = self.promo(code)?;
+        ensure!(promo.active, "Promo code {code} is already disabled");
+        self.storage.update_promo(PromoCode {
+            active: false,
+            ..promo
+        })
+    }
+
+    pub(super) fn redeem(
+        &self,
+        code: &str,
+        customer: CustomerId,
+        arrival: &Date,
+        base: &Price,
+    ) -> Result<AppliedPromo> {
+        let promo = self.promo(code)?;
+        ensure!(promo.active, "Promo code {code} has been disabled");
+        ensure!(
+            promo.valid.contains(arrival),
+            "Promo code {code} is only valid for arrivals from {} until {}",
+            promo.valid.start,
+            promo.valid.end,
+        );
+        let uses = self.storage.bookings_with_promo(code)?;
+        ensure!(
+            promo.limit.allows(uses.len()),
+            "Promo code {code} has been used up",
+        );
+        let customer_uses = uses
+            .iter()
+            .filter(|booking| booking.customer == customer)
+            .count();
+        ensure!(
+            promo.customer_limit.allows(customer_uses),
+            "Customer {customer} has already used promo code {code}",
+        );
+        let discount = match promo.discount {
+            Discount::Percent(percent) => base.percent(percent),
+            Discount::Fixed(price) => self.exchange(&price, base.currency)?.min(base.clone()),
+        };
+        Ok(AppliedPromo {
+            code: promo.code,
+            discount,
+        })
+    }
+    fn promo(&self, code: &str) -> Result<PromoCode> {
+        self.storage
+            .promo(code)?
+            .ok_or_else(|| anyhow::format_err!("Unknown promo code {code}"))
+    }
+}
@@ -0,0 +1,747 @@
+//! A snapshot starts with a header line naming the format version, followed by one record per line.
+//! Each record is a keyword followed by whitespace-separated fields. Free-text fields are
+//! percent-escaped so that they never contain whitespace.
+
+use std::{
+    collections::HashSet,
+    fmt::{Display, Write as _},
+    fs,
+    io::Write as _,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::{FromStr, SplitWhitespace},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use super::{
+    Model, category,
+    storage::{self, Counters, MemoryStorage, Storage},
+    turnover,
+};
+use crate::data::{
+    AppliedPromo, Booking, BookingId, Category, Charge, Currency, Customer, CustomerDetails,
+    CustomerId, Date, Discount, HotelData, HotelDetails, HotelId, Person, Price, PricingRule,
+    PromoCode, RatePeriod, RoomData, RoomId,
+};
+
+const MAGIC: &str = "a4native-snapshot";
+const VERSION: u32 = 1;
+
+const RECORD_COUNTERS: &str = "counters";
+const RECORD_SETTING: &str = "setting";
+const RECORD_EXCHANGE: &str = "exchange";
+const RECORD_CATEGORY: &str = "category";
+const RECORD_HOTEL: &str = "hotel";
+const RECORD_RULE: &str = "rule";
+const RECORD_CHARGE: &str = "charge";
+const RECORD_ROOM: &str = "room";
+const RECORD_RATE: &str = "rate";
+const RECORD_PROMO: &str = "promo";
+const RECORD_BOOKING: &str = "booking";
+const RECORD_CUSTOMER: &str = "customer";
+
+const PROMO_ACTIVE: &str = "active";
+const PROMO_DISABLED: &str = "disabled";
+const UNSET: &str = "-";
+const LIST_SEPARATOR: char = ',';
+
+impl Model {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let snapshot = self.to_snapshot()?;
+        // Write to a sibling file first so that a crash never leaves a half-written snapshot
+        let mut tmp = PathBuf::from(path);
+        tmp.as_mut_os_string().push(".tmp");
+        let mut file = fs::File::create(&tmp)
+            .with_context(|| format!("Cannot create snapshot file {}", tmp.display()))?;
+        file.write_all(snapshot.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Cannot write snapshot file {}", path.display()))?;
+        Ok(())
+    }
+
+    /// The model is left untouched if the snapshot is invalid.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let snapshot = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read snapshot file {}", path.display()))?;
+        let loaded = Self::from_snapshot(&snapshot)?;
+        self.atomically(|model| storage::copy(&loaded, &mut *model.storage))
+    }
+
+    fn to_snapshot(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "{MAGIC} {VERSION}")?;
+        let Counters { booking, customer } = self.storage.counters()?;
+        writeln!(out, "{RECORD_COUNTERS} {booking} {customer}")?;
+
+        let mut settings = self.storage.settings()?;
+        settings.sort_unstable();
+        for (key, value) in &settings {
+            writeln!(out, "{RECORD_SETTING} {} {}", escape(key), escape(value))?;
+        }
+
+        let mut rates = self.storage.exchange_rates()?;
+        rates.sort_unstable_by_key(|&(currency, _)| currency);
+        for (currency, rate) in &rates {
+            writeln!(out, "{RECORD_EXCHANGE} {currency} {rate}")?;
+        }
+
+        let mut categories = self.storage.categories()?;
+        categories.sort_unstable();
+        for (category, capacity) in &categories {
+            writeln!(out, "{RECORD_CATEGORY} {category} {capacity}")?;
+        }
+
+        let mut hotels = self.storage.hotels()?;
+        hotels.sort_unstable_by_key(|&(id, _)| id);
+        for (
+            hotel_id,
+            HotelData {
+                city,
+                currency,
+                details:
+                    HotelDetails {
+                        name,
+                        address,
+                        stars,
+                        check_in,
+                        check_out,
+                        contact,
+                    },
+            },
+        ) in &hotels
+        {
+            writeln!(
+                out,
+                "{RECORD_HOTEL} {hotel_id} {} {currency} {} {} {} {} {} {}",
+                escape(city),
+                escape_optional(name.as_deref()),
+                escape_optional(address.as_deref()),
+                optional(stars),
+                optional(check_in),
+                optional(check_out),
+                escape_optional(contact.as_deref()),
+            )?;
+        }
+
+        let mut rules = self.storage.all_rules()?;
+        rules.sort_unstable_by(|(h1, a), (h2, b)| (h1, &a.name).cmp(&(h2, &b.name)));
+        for (
+            hotel_id,
+            PricingRule {
+                name,
+                scope,
+                condition,
+                adjustment,
+            },
+        ) in &rules
+        {
+            writeln!(
+                out,
+                "{RECORD_RULE} {hotel_id} {} {scope} {condition} {adjustment}",
+                escape(name),
+            )?;
+        }
+
+        let mut charges = self.storage.all_charges()?;
+        charges.sort_unstable_by(|(c1, a), (c2, b)| (c1, &a.name).cmp(&(c2, &b.name)));
+        for (
+            city,
+            Charge {
+                name,
+                kind,
+                scope,
+                amount,
+            },
+        ) in &charges
+        {
+            writeln!(
+                out,
+                "{RECORD_CHARGE} {} {} {kind} {scope} {}",
+                escape(city),
+                escape(name),
+                Amount(amount),
+            )?;
+        }
+
+        let mut rooms = self.storage.rooms()?;
+        rooms.sort_unstable_by_key(|&(hotel, room, _)| (hotel, room));
+        for (
+            hotel_id,
+            room_id,
+            RoomData {
+                category,
+                price,
+                capacity,
+                supplement,
+                amenities,
+            },
+        ) in &rooms
+        {
+            let supplement = match supplement {
+                Some(supplement) => Amount(supplement).to_string(),
+                None => UNSET.into(),
+            };
+            let amenities = match amenities.is_empty() {
+                true => UNSET.into(),
+                false => {
+                    let amenities: Vec<_> = amenities.iter().map(ToString::to_string).collect();
+                    amenities.join(&LIST_SEPARATOR.to_string())
+                }
+            };
+            writeln!(
+                out,
+                "{RECORD_ROOM} {hotel_id} {room_id} {category} {} {capacity} {supplement} \
+                {amenities}",
+                Amount(price),
+            )?;
+        }
+
+        let mut rates = self.storage.all_rates()?;
+        rates.sort_unstable_by(|(h1, r1, a), (h2, r2, b)| {
+            (h1, r1, &a.time.start, a.kind).cmp(&(h2, r2, &b.time.start, b.kind))
+        });
+        for (hotel_id, room_id, RatePeriod { kind, time, price }) in &rates {
+            writeln!(
+                out,
+                "{RECORD_RATE} {hotel_id} {room_id} {kind} {} {} {}",
+                time.start,
+                time.end,
+                Amount(price),
+            )?;
+        }
+
+        let mut promos = self.storage.promos()?;
+        promos.sort_unstable_by(|a, b| a.code.cmp(&b.code));
+        for PromoCode {
+            code,
+            discount,
+            valid: Range { start, end },
+            limit,
+            customer_limit,
+            active,
+        } in &promos
+        {
+            let state = if *active {
+                PROMO_ACTIVE
+            } else {
+                PROMO_DISABLED
+            };
+            // Fixed discounts are stored in cents like every other price
+            let discount = match discount {
+                Discount::Percent(_) => discount.to_string(),
+                Discount::Fixed(price) => Amount(price).to_string(),
+            };
+            writeln!(
+                out,
+                "{RECORD_PROMO} {} {discount} {start} {end} {limit} {customer_limit} {state}",
+                escape(code),
+            )?;
+        }
+
+        let mut bookings = self.storage.bookings()?;
+        bookings.sort_unstable_by_key(|booking| booking.id);
+        for Booking {
+            hotel,
+            room,
+            time: Range { start, end },
+            customer,
+            id,
+            guests,
+            rate,
+            total,
+            promo,
+        } in &bookings
+        {
+            write!(
+                out,
+                "{RECORD_BOOKING} {hotel} {room} {id} {customer} {start} {end} {} {} {guests}",
+                Amount(rate),
+                Amount(total),
+            )?;
+            if let Some(AppliedPromo { code, discount }) = promo {
+                write!(out, " {} {}", escape(code), Amount(discount))?;
+            }
+            writeln!(out)?;
+        }
+
+        let mut customers = self.storage.customers()?;
+        customers.sort_unstable_by_key(|&(id, _)| id);
+        for (
+            id,
+            Customer {
+                person: Person { forename, surname },
+                details:
+                    CustomerDetails {
+                        email,
+                        phone,
+                        birth_date,
+                        address,
+                    },
+            },
+        ) in &customers
+        {
+            writeln!(
+                out,
+                "{RECORD_CUSTOMER} {id} {} {} {} {} {} {}",
+                escape(forename),
+                escape(surname),
+                escape_optional(email.as_deref()),
+                escape_optional(phone.as_deref()),
+                optional(birth_date),
+                escape_optional(address.as_deref()),
+            )?;
+        }
+        Ok(out)
+    }
+
+    fn from_snapshot(snapshot: &str) -> Result<MemoryStorage> {
+        let mut lines = snapshot.lines().enumerate();
+        let Some((_, header)) = lines.next() else {
+            bail!("Snapshot is empty")
+        };
+        let Some(version) = header.strip_prefix(MAGIC).and_then(|rest| {
+            rest.strip_prefix(' ')
+                .and_then(|version| u32::from_str(version).ok())
+        }) else {
+            bail!("Not a snapshot file")
+        };
+        ensure!(
+            version <= VERSION,
+            "Snapshot version {version} is newer than the supported version {VERSION}",
+        );
+
+        let mut reader = SnapshotReader {
+            storage: MemoryStorage::default(),
+            booking_ids: Default::default(),
+            customer_ids: Default::default(),
+        };
+        for (idx, line) in lines {
+            reader
+                .read_record(line)
+                .with_context(|| format!("Invalid snapshot record on line {}", idx + 1))?;
+        }
+        let SnapshotReader {
+            storage,
+            booking_ids,
+            customer_ids,
+        } = reader;
+        let counters = storage.counters()?;
+        ensure!(
+            booking_ids.iter().all(|&id| id < counters.booking)
+                && customer_ids.iter().all(|&id| id < counters.customer),
+            "Snapshot counters are behind the stored ids",
+        );
+        Ok(storage)
+    }
+}
+
+struct SnapshotReader {
+    storage: MemoryStorage,
+    booking_ids: HashSet<BookingId>,
+    customer_ids: HashSet<CustomerId>,
+}
+impl SnapshotReader {
+    fn read_record(&mut self, line: &str) -> Result<()> {
+        let Self {
+            storage,
+            booking_ids,
+            customer_ids,
+        } = self;
+        let mut fields = Fields(line.split_whitespace());
+        let Some(kind) = fields.0.next() else {
+            return Ok(());
+        };
+        match kind {
+            RECORD_COUNTERS => {
+                storage.set_counters(Counters {
+                    booking: fields.next()?,
+                    customer: fields.next()?,
+                })?;
+            }
+            RECORD_SETTING => {
+                let key = fields.next_text()?;
+                let value = fields.next_text()?;
+                storage.set_setting(&key, &value)?;
+            }
+            RECORD_EXCHANGE => {
+                let currency: Currency = fields.next()?;
+                let rate = fields.next()?;
+                let mut rates = storage.exchange_rates()?;
+                ensure!(
+                    rates.iter().all(|&(other, _)| other != currency),
+                    "Duplicate exchange rate for {currency}",
+                );
+                rates.push((currency, rate));
+                storage.set_exchange_rates(rates)?;
+            }
+            RECORD_CATEGORY => {
+                let category = fields.next()?;
+                let capacity = fields.next()?;
+                category::ensure_new(storage, &category, capacity)?;
+                storage.insert_category(category, capacity)?;
+            }
+            RECORD_HOTEL => {
+                let id: HotelId = fields.next()?;
+                let city = fields.next_text()?;
+                let currency = fields.next()?;
+                let details = HotelDetails {
+                    name: fields.next_optional_text()?,
+                    address: fields.next_optional_text()?,
+                    stars: fields.next_optional()?,
+                    check_in: fields.next_optional()?,
+                    check_out: fields.next_optional()?,
+                    contact: fields.next_optional_text()?,
+                };
+                ensure!(storage.hotel(id)?.is_none(), "Duplicate hotel {id}");
+                storage.insert_hotel(
+                    id,
+                    HotelData {
+                        city,
+                        currency,
+                        details,
+                    },
+                )?;
+            }
+            RECORD_RULE => {
+                let hotel_id: HotelId = fields.next()?;
+                let rule = PricingRule {
+                    name: fields.next_text()?,
+                    scope: fields.next()?,
+                    condition: fields.next()?,
+                    adjustment: fields.next()?,
+                };
+                ensure!(
+                    storage.hotel(hotel_id)?.is_some(),
+                    "Unknown hotel ID {hotel_id}",
+                );
+                category::ensure_scope(storage, &rule.scope)?;
+                ensure!(
+                    storage
+                        .rules(hotel_id)?
+                        .iter()
+                        .all(|other| other.name != rule.name),
+                    "Duplicate rule {} in hotel {hotel_id}",
+                    rule.name,
+                );
+                storage.insert_rule(hotel_id, rule)?;
+            }
+            RECORD_CHARGE => {
+                let city = fields.next_text()?;
+                let charge = Charge {
+                    name: fields.next_text()?,
+                    kind: fields.next()?,
+                    scope: fields.next()?,
+                    amount: fields.next_price()?,
+                };
+                ensure!(
+                    storage
+                        .charges(&city)?
+                        .iter()
+                        .all(|other| other.name != charge.name),
+                    "Duplicate charge {} in {city}",
+                    charge.name,
+                );
+                category::ensure_scope(storage, &charge.scope)?;
+                storage.insert_charge(&city, charge)?;
+            }
+            RECORD_ROOM => {
+                let hotel_id: HotelId = fields.next()?;
+                let room_id: RoomId = fields.next()?;
+                let category: Category = fields.next()?;
+                let price = fields.next_price()?;
+                let capacity = fields.next()?;
+                let supplement = match fields.0.clone().next() {
+                    Some(UNSET) => {
+                        fields.0.next();
+                        None
+                    }
+                    _ => Some(fields.next_price()?),
+                };
+                let amenities = match fields.next::<String>()? {
+                    amenities if amenities == UNSET => Default::default(),
+                    amenities => amenities
+                        .split(LIST_SEPARATOR)
+                        .map(str::parse)
+                        .collect::<Result<_>>()?,
+                };
+                ensure!(capacity > 0, "Rooms must sleep at least one guest");
+                ensure!(
+                    storage.hotel(hotel_id)?.is_some(),
+                    "Unknown hotel ID {hotel_id}",
+                );
+                ensure!(
+                    storage.room(hotel_id, room_id)?.is_none(),
+                    "Duplicate room {room_id} in hotel {hotel_id}",
+                );
+                storage.insert_room(
+                    hotel_id,
+                    room_id,
+                    RoomData {
+                        category,
+                        price,
+                        capacity,
+                        supplement,
+                        amenities,
+                    },
+                )?;
+            }
+            RECORD_RATE => {
+                let hotel_id: HotelId = fields.next()?;
+                let room_id: RoomId = fields.next()?;
+                let kind = fields.next()?;
+                let time = fields.next()?..fields.next()?;
+                let price = fields.next_price()?;
+                ensure!(time.start < time.end, "Invalid date range");
+                ensure!(
+                    storage.room(hotel_id, room_id)?.is_some(),
+                    "Unknown room {room_id} in hotel {hotel_id}",
+                );
+                storage.insert_rate(hotel_id, room_id, RatePeriod { kind, time, price })?;
+            }
+            RECORD_PROMO => {
+                let promo = PromoCode {
+                    code: fields.next_text()?,
+                    discount: fields.next_discount()?,
+                    valid: fields.next()?..fields.next()?,
+                    limit: fields.next()?,
+                    customer_limit: fields.next()?,
+                    active: match fields.0.next() {
+                        Some(PROMO_ACTIVE) => true,
+                        Some(PROMO_DISABLED) => false,
+                        _ => bail!("Invalid promo code state"),
+                    },
+                };
+                ensure!(promo.valid.start < promo.valid.end, "Invalid date range");
+                ensure!(
+                    storage.promo(&promo.code)?.is_none(),
+                    "Duplicate promo code {}",
+                    promo.code,
+                );
+                storage.insert_promo(promo)?;
+            }
+            RECORD_BOOKING => {
+                let hotel: HotelId = fields.next()?;
+                let room: RoomId = fields.next()?;
+                let id = fields.next()?;
+                let customer = fields.next()?;
+                let time: Range<Date> = fields.next()?..fields.next()?;
+                ensure!(time.start < time.end, "Invalid date range");
+                ensure!(booking_ids.insert(id), "Duplicate booking {id}");
+                ensure!(
+                    storage.room(hotel, room)?.is_some(),
+                    "Unknown room {room} in hotel {hotel}",
+                );
+                let rate = fields.next_price()?;
+                // A discount can take the total down to zero
+                let total = fields.next_amount()?;
+                let guests = fields.next()?;
+                ensure!(guests > 0, "Bookings must have at least one guest");
+                // Settings come first, so the turnover is known by now
+                ensure!(
+                    !storage.is_occupied(
+                        hotel,
+                        room,
+                        &time.start..&time.end,
+                        turnover(storage)?
+                    )?,
+                    "Booking {id} overlaps another booking of room {room} in hotel {hotel}",
+                );
+                let promo = match fields.0.next() {
+                    Some(code) => Some(AppliedPromo {
+                        code: unescape(code)?,
+                        discount: fields.next_amount()?,
+                    }),
+                    None => None,
+                };
+                storage.insert_booking(Booking {
+                    hotel,
+                    room,
+                    time,
+                    customer,
+                    id,
+                    guests,
+                    rate,
+                    total,
+                    promo,
+                })?;
+            }
+            RECORD_CUSTOMER => {
+                let id = fields.next()?;
+                let person = Person {
+                    forename: fields.next_text()?,
+                    surname: fields.next_text()?,
+                };
+                let details = CustomerDetails {
+                    email: fields.next_optional_text()?,
+                    phone: fields.next_optional_text()?,
+                    birth_date: fields.next_optional()?,
+                    address: fields.next_optional_text()?,
+                };
+                // Customers with the same name may be different people
+                ensure!(customer_ids.insert(id), "Duplicate customer {id}");
+                storage.insert_customer(id, Customer { person, details })?;
+            }
+            _ => bail!("Unknown record '{kind}'"),
+        }
+        ensure!(fields.0.next().is_none(), "Too many fields");
+        Ok(())
+    }
+}
+
+struct Fields<'a>(SplitWhitespace<'a>);
+impl Fields<'_> {
+    fn next<T>(&mut self) -> Result<T>
+    where
+        T: FromStr,
+        anyhow::Error: From<T::Err>,
+    {
+        let Some(field) = self.0.next() else {
+            bail!("Missing field")
+        };
+        Ok(T::from_str(field)?)
+    }
+    fn next_amount(&mut self) -> Result<Price> {
+        let Some(field) = self.0.next() else {
+            bail!("Missing field")
+        };
+        let cents = field.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        Ok(Price {
+            cents: cents.parse()?,
+            currency: field[cents.len()..].parse()?,
+        })
+    }
+    fn next_price(&mut self) -> Result<Price> {
+        let price = self.next_amount()?;
+        ensure!(!price.is_zero(), "Price must be non-zero");
+        Ok(price)
+    }
+    fn next_discount(&mut self) -> Result<Discount> {
+        if self
+            .0
+            .clone()
+            .next()
+            .is_some_and(|field| field.ends_with('%'))
+        {
+            self.next()
+        } else {
+            Ok(Discount::Fixed(self.next_price()?))
+        }
+    }
+    fn next_text(&mut self) -> Result<String> {
+        let Some(field) = self.0.next() else {
+            bail!("Missing field")
+        };
+        unescape(field)
+    }
+    fn next_optional<T>(&mut self) -> Result<Option<T>>
+    where
+        T: FromStr,
+        anyhow::Error: From<T::Err>,
+    {
+        match self.0.clone().next() {
+            Some(UNSET) => {
+                self.0.next();
+                Ok(None)
+            }
+            _ => Ok(Some(self.next()?)),
+        }
+    }
+    fn next_optional_text(&mut self) -> Result<Option<String>> {
+        match self.0.clone().next() {
+            Some(UNSET) => {
+                self.0.next();
+                Ok(None)
+            }
+            _ => Ok(Some(self.next_text()?)),
+        }
+    }
+}
+
+fn optional(value: &Option<impl Display>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => UNSET.into(),
+    }
+}
+fn escape_optional(text: Option<&str>) -> String {
+    match text {
+        // Text that reads like an unset field is escaped as a whole
+        Some(UNSET) => UNSET.bytes().map(|byte| format!("%{byte:02X}")).collect(),
+        Some(text) => escape(text),
+        None => UNSET.into(),
+    }
+}
+
+struct Amount<'a>(&'a Price);
+impl std::fmt::Display for Amount<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.0.cents, self.0.currency)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if ch == '%' || ch.is_whitespace() {
+            let mut buf = [0; 4];
+            for byte in ch.encode_utf8(&mut buf).bytes() {
+                write!(escaped, "%{byte:02X}").unwrap();
+            }
+        } else {
+            escaped.push(ch);
+        }
+    }
+    escaped
+}
+fn unescape(field: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2).context("Truncated escape sequence")?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex)?, 16)?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::CustomerRef;
+
+    #[test]
+    fn overlapping_bookings_are_rejected() {
+        let mut model = Model::new();
+        let hotel = HotelId::try_from(1).unwrap();
+        let date = |date: &str| date.parse::<Date>().unwrap();
+        model
+            .add_hotel(hotel, "Basel".into(), Currency::EUR)
+            .unwrap();
+        let price = "100".parse().unwrap();
+        model
+            .add_room(hotel, 101, "Single".parse().unwrap(), price, None)
+            .unwrap();
+        for time in [
+            date("2030-01-10")..date("2030-01-15"),
+            date("2030-01-15")..date("2030-01-17"),
+        ] {
+            let customer = CustomerRef::Name(Person {
+                forename: "Max".into(),
+                surname: "Muster".into(),
+            });
+            model.book(hotel, 101, time, 1, customer, None).unwrap();
+        }
+        let snapshot = model.to_snapshot().unwrap();
+        assert!(Model::from_snapshot(&snapshot).is_ok());
+        let overlapping = snapshot.replace("2030-01-15 2030-01-17", "2030-01-14 2030-01-17");
+        assert_ne!(overlapping, snapshot);
+        assert!(Model::from_snapshot(&overlapping).is_err());
+    }
+}
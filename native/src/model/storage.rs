@@ -0,0 +1,416 @@
+mod memory;
+mod sqlite;
+
+pub use memory::MemoryStorage;
+pub use sqlite::SqliteStorage;
+
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::data::{
+    Booking, BookingId, Category, Charge, Currency, Customer, CustomerId, Date, ExchangeRate,
+    HotelData, HotelId, PricingRule, PromoCode, RateKind, RatePeriod, RoomData, RoomId, Turnover,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counters {
+    pub booking: BookingId,
+    pub customer: CustomerId,
+}
+impl Default for Counters {
+    fn default() -> Self {
+        Self {
+            booking: 1,
+            customer: 1,
+        }
+    }
+}
+
+/// Backends do not enforce the model's invariants. The `insert_*` methods may assume that the key
+/// is not in use yet and that the records referenced by it exist. Removing a hotel or room also
+/// removes everything stored under it. Cities are looked up by their [`fold`](crate::data::fold)ed
+/// spelling, but returned as they were stored.
+pub trait Storage {
+    fn counters(&self) -> Result<Counters>;
+    fn set_counters(&mut self, counters: Counters) -> Result<()>;
+
+    fn exchange_rate(&self, currency: Currency) -> Result<Option<ExchangeRate>>;
+    fn exchange_rates(&self) -> Result<Vec<(Currency, ExchangeRate)>>;
+    fn set_exchange_rates(&mut self, rates: Vec<(Currency, ExchangeRate)>) -> Result<()>;
+
+    /// The categories that were added, without the built-in ones.
+    fn categories(&self) -> Result<Vec<(Category, u32)>>;
+    fn insert_category(&mut self, category: Category, capacity: u32) -> Result<()>;
+
+    fn setting(&self, key: &str) -> Result<Option<String>>;
+    fn settings(&self) -> Result<Vec<(String, String)>>;
+    fn set_setting(&mut self, key: &str, value: &str) -> Result<()>;
+
+    fn hotel(&self, id: HotelId) -> Result<Option<HotelData>>;
+    fn hotels(&self) -> Result<Vec<(HotelId, HotelData)>>;
+    fn insert_hotel(&mut self, id: HotelId, hotel: HotelData) -> Result<()>;
+    fn update_hotel(&mut self, id: HotelId, hotel: HotelData) -> Result<()>;
+    fn remove_hotel(&mut self, id: HotelId) -> Result<bool>;
+
+    fn rules(&self, hotel: HotelId) -> Result<Vec<PricingRule>>;
+    fn all_rules(&self) -> Result<Vec<(HotelId, PricingRule)>>;
+    fn insert_rule(&mut self, hotel: HotelId, rule: PricingRule) -> Result<()>;
+    fn remove_rule(&mut self, hotel: HotelId, name: &str) -> Result<bool>;
+
+    fn charges(&self, city: &str) -> Result<Vec<Charge>>;
+    fn all_charges(&self) -> Result<Vec<(String, Charge)>>;
+    fn insert_charge(&mut self, city: &str, charge: Charge) -> Result<()>;
+    fn remove_charge(&mut self, city: &str, name: &str) -> Result<bool>;
+
+    fn room(&self, hotel: HotelId, room: RoomId) -> Result<Option<RoomData>>;
+    fn rooms(&self) -> Result<Vec<(HotelId, RoomId, RoomData)>>;
+    fn rooms_in_city(&self, city: &str) -> Result<Vec<(HotelId, RoomId, RoomData)>>;
+    fn insert_room(&mut self, hotel: HotelId, room: RoomId, data: RoomData) -> Result<()>;
+    fn update_room(&mut self, hotel: HotelId, room: RoomId, data: RoomData) -> Result<()>;
+    fn remove_room(&mut self, hotel: HotelId, room: RoomId) -> Result<bool>;
+
+    fn rates(&self, hotel: HotelId, room: RoomId) -> Result<Vec<RatePeriod>>;
+    fn all_rates(&self) -> Result<Vec<(HotelId, RoomId, RatePeriod)>>;
+    fn insert_rate(&mut self, hotel: HotelId, room: RoomId, rate: RatePeriod) -> Result<()>;
+    fn remove_rate(
+        &mut self,
+        hotel: HotelId,
+        room: RoomId,
+        kind: RateKind,
+        start: &Date,
+    ) -> Result<bool>;
+
+    fn booking(&self, id: BookingId) -> Result<Option<Booking>>;
+    fn bookings(&self) -> Result<Vec<Booking>>;
+    fn bookings_of(&self, hotel: HotelId, room: Option<RoomId>) -> Result<Vec<Booking>>;
+    fn is_occupied(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+    ) -> Result<bool>;
+    fn is_occupied_except(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+        except: BookingId,
+    ) -> Result<bool>;
+    /// Fails if the booking shares a night with another booking of its room, as the occupancy
+    /// queries only look at the last booking starting before a stay ends.
+    fn insert_booking(&mut self, booking: Booking) -> Result<()>;
+    fn remove_booking(&mut self, id: BookingId) -> Result<bool>;
+
+    fn bookings_with_promo(&self, code: &str) -> Result<Vec<Booking>>;
+    fn bookings_of_customer(&self, customer: CustomerId) -> Result<Vec<Booking>>;
+
+    fn promo(&self, code: &str) -> Result<Option<PromoCode>>;
+    fn promos(&self) -> Result<Vec<PromoCode>>;
+    fn insert_promo(&mut self, promo: PromoCode) -> Result<()>;
+    fn update_promo(&mut self, promo: PromoCode) -> Result<()>;
+
+    fn customer(&self, id: CustomerId) -> Result<Option<Customer>>;
+    fn customers(&self) -> Result<Vec<(CustomerId, Customer)>>;
+    fn insert_customer(&mut self, id: CustomerId, customer: Customer) -> Result<()>;
+    fn update_customer(&mut self, id: CustomerId, customer: Customer) -> Result<()>;
+    fn remove_customer(&mut self, id: CustomerId) -> Result<bool>;
+
+    fn clear(&mut self) -> Result<()>;
+
+    /// Groups of changes nest.
+    fn begin(&mut self) -> Result<()>;
+    fn commit(&mut self) -> Result<()>;
+    fn rollback(&mut self) -> Result<()>;
+}
+
+pub fn copy(from: &dyn Storage, to: &mut dyn Storage) -> Result<()> {
+    to.clear()?;
+    to.set_counters(from.counters()?)?;
+    for (key, value) in from.settings()? {
+        to.set_setting(&key, &value)?;
+    }
+    to.set_exchange_rates(from.exchange_rates()?)?;
+    for (category, capacity) in from.categories()? {
+        to.insert_category(category, capacity)?;
+    }
+    for (id, hotel) in from.hotels()? {
+        to.insert_hotel(id, hotel)?;
+    }
+    for (hotel, rule) in from.all_rules()? {
+        to.insert_rule(hotel, rule)?;
+    }
+    for (city, charge) in from.all_charges()? {
+        to.insert_charge(&city, charge)?;
+    }
+    for (hotel, room, data) in from.rooms()? {
+        to.insert_room(hotel, room, data)?;
+    }
+    for (hotel, room, rate) in from.all_rates()? {
+        to.insert_rate(hotel, room, rate)?;
+    }
+    for promo in from.promos()? {
+        to.insert_promo(promo)?;
+    }
+    for booking in from.bookings()? {
+        to.insert_booking(booking)?;
+    }
+    for (id, customer) in from.customers()? {
+        to.insert_customer(id, customer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, fmt::Debug};
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::data::{HotelDetails, Price};
+
+    const ROOMS: RoomId = 3;
+    const DAYS: usize = 60;
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Book {
+            room: RoomId,
+            start: usize,
+            nights: usize,
+        },
+        Cancel(usize),
+    }
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            3 => (0..ROOMS, 0..DAYS - 8, 1..8usize)
+                .prop_map(|(room, start, nights)| Op::Book { room, start, nights }),
+            1 => any::<usize>().prop_map(Op::Cancel),
+        ]
+    }
+    fn add_rooms(storage: &mut dyn Storage) -> HotelId {
+        let hotel = HotelId::try_from(1).unwrap();
+        let data = HotelData {
+            city: "Karlsruhe".into(),
+            currency: Currency::EUR,
+            details: HotelDetails::default(),
+        };
+        storage.insert_hotel(hotel, data).unwrap();
+        for room in 0..ROOMS {
+            let data = RoomData {
+                category: "Single".parse().unwrap(),
+                price: price(),
+                capacity: 1,
+                supplement: None,
+                amenities: BTreeSet::new(),
+            };
+            storage.insert_room(hotel, room, data).unwrap();
+        }
+        hotel
+    }
+    fn price() -> Price {
+        Price {
+            cents: 100u32.into(),
+            currency: Currency::EUR,
+        }
+    }
+    fn booking(hotel: HotelId, room: RoomId, id: BookingId, time: Range<Date>) -> Booking {
+        Booking {
+            hotel,
+            room,
+            time,
+            customer: 1,
+            id,
+            guests: 1,
+            rate: price(),
+            total: price(),
+            promo: None,
+        }
+    }
+
+    #[test]
+    fn overlapping_bookings_are_rejected() {
+        let memory = Box::new(MemoryStorage::default());
+        let sqlite = Box::new(SqliteStorage::open(":memory:".as_ref()).unwrap());
+        for mut storage in [memory as Box<dyn Storage>, sqlite] {
+            let hotel = add_rooms(&mut *storage);
+            let date = |date: &str| date.parse::<Date>().unwrap();
+            let stay = date("2030-01-10")..date("2030-01-15");
+            storage
+                .insert_booking(booking(hotel, 0, 1, stay.clone()))
+                .unwrap();
+            let same_start = date("2030-01-10")..date("2030-01-12");
+            let enclosing = date("2030-01-09")..date("2030-01-16");
+            for (id, time) in [(2, same_start), (3, enclosing)] {
+                assert!(storage.insert_booking(booking(hotel, 0, id, time)).is_err());
+            }
+            assert_eq!(
+                storage.booking(1).unwrap().map(|booking| booking.time),
+                Some(stay)
+            );
+            let next = date("2030-01-15")..date("2030-01-17");
+            storage.insert_booking(booking(hotel, 0, 4, next)).unwrap();
+            storage
+                .insert_booking(booking(hotel, 1, 5, date("2030-01-10")..date("2030-01-12")))
+                .unwrap();
+        }
+    }
+
+    // Everything in a storage, in an order that does not depend on the backend
+    fn contents(storage: &dyn Storage) -> Vec<String> {
+        fn sorted<T: Debug>(records: Vec<T>) -> String {
+            let mut records: Vec<_> = records.iter().map(|record| format!("{record:?}")).collect();
+            records.sort_unstable();
+            records.join("\n")
+        }
+        vec![
+            format!("{:?}", storage.counters().unwrap()),
+            sorted(storage.settings().unwrap()),
+            sorted(storage.exchange_rates().unwrap()),
+            sorted(storage.categories().unwrap()),
+            sorted(storage.hotels().unwrap()),
+            sorted(storage.all_rules().unwrap()),
+            sorted(storage.all_charges().unwrap()),
+            sorted(storage.rooms().unwrap()),
+            sorted(storage.all_rates().unwrap()),
+            sorted(storage.promos().unwrap()),
+            sorted(storage.bookings().unwrap()),
+            sorted(storage.customers().unwrap()),
+        ]
+    }
+
+    #[test]
+    fn rolled_back_changes_are_undone() {
+        let memory = Box::new(MemoryStorage::default());
+        let sqlite = Box::new(SqliteStorage::open(":memory:".as_ref()).unwrap());
+        for mut storage in [memory as Box<dyn Storage>, sqlite] {
+            let hotel = add_rooms(&mut *storage);
+            let stay = "2030-01-10".parse().unwrap().."2030-01-15".parse().unwrap();
+            storage
+                .insert_booking(booking(hotel, 0, 1, stay.clone()))
+                .unwrap();
+            storage.set_setting("turnover", "next-day").unwrap();
+            let before = contents(&*storage);
+
+            storage.begin().unwrap();
+            storage.remove_booking(1).unwrap();
+            storage
+                .insert_booking(booking(hotel, 0, 2, stay.clone()))
+                .unwrap();
+            storage.begin().unwrap();
+            storage.remove_room(hotel, 0).unwrap();
+            storage.set_setting("turnover", "same-day").unwrap();
+            storage.commit().unwrap();
+            storage.remove_hotel(hotel).unwrap();
+            storage.clear().unwrap();
+            storage.insert_category("Loft".parse().unwrap(), 3).unwrap();
+            storage.rollback().unwrap();
+            assert_eq!(contents(&*storage), before);
+            assert!(storage.booking(1).unwrap().is_some());
+
+            // Undoing an inner group keeps the changes of the outer one
+            storage.begin().unwrap();
+            storage.remove_booking(1).unwrap();
+            storage.begin().unwrap();
+            storage.remove_room(hotel, 1).unwrap();
+            storage.rollback().unwrap();
+            storage.commit().unwrap();
+            assert!(storage.booking(1).unwrap().is_none());
+            assert!(storage.room(hotel, 1).unwrap().is_some());
+            storage.begin().unwrap();
+            storage.rollback().unwrap();
+            assert!(storage.booking(1).unwrap().is_none());
+        }
+    }
+
+    fn share_a_day(a: &Range<usize>, b: &Range<usize>, turnover: Turnover) -> bool {
+        match turnover {
+            Turnover::SameDay => a.clone().any(|night| b.contains(&night)),
+            Turnover::NextDay => (a.start..=a.end).any(|day| (b.start..=b.end).contains(&day)),
+        }
+    }
+
+    fn book_and_cancel(
+        storage: &mut dyn Storage,
+        turnover: Turnover,
+        ops: &[Op],
+    ) -> Result<(), TestCaseError> {
+        let days: Vec<Date> = {
+            let start: Date = "2030-01-01".parse().unwrap();
+            let end: Date = "2031-01-01".parse().unwrap();
+            start.nights_until(&end).take(DAYS).collect()
+        };
+        let hotel = add_rooms(storage);
+
+        // The bookings that were made and not cancelled, with their stays as day numbers
+        let mut booked: Vec<(BookingId, RoomId, Range<usize>)> = Vec::new();
+        for (id, op) in (1..).zip(ops) {
+            match *op {
+                Op::Book {
+                    room,
+                    start,
+                    nights,
+                } => {
+                    let stay = start..start + nights;
+                    let occupied = storage
+                        .is_occupied(hotel, room, &days[stay.start]..&days[stay.end], turnover)
+                        .unwrap();
+                    let expected = booked.iter().any(|(_, booked_room, booked_stay)| {
+                        *booked_room == room && share_a_day(booked_stay, &stay, turnover)
+                    });
+                    prop_assert_eq!(occupied, expected, "{:?} in room {}", stay, room);
+                    if !occupied {
+                        let time = days[stay.start].clone()..days[stay.end].clone();
+                        storage
+                            .insert_booking(booking(hotel, room, id, time))
+                            .unwrap();
+                        booked.push((id, room, stay));
+                    }
+                }
+                Op::Cancel(idx) if !booked.is_empty() => {
+                    let (id, ..) = booked.swap_remove(idx % booked.len());
+                    prop_assert!(storage.remove_booking(id).unwrap());
+                }
+                Op::Cancel(_) => {}
+            }
+        }
+
+        let mut bookings = storage.bookings().unwrap();
+        prop_assert_eq!(bookings.len(), booked.len());
+        bookings.sort_unstable_by_key(|booking| booking.id);
+        for (idx, a) in bookings.iter().enumerate() {
+            for b in &bookings[idx + 1..] {
+                let day = |date: &Date| days.iter().position(|day| day == date).unwrap();
+                let (a_stay, b_stay) = (
+                    day(&a.time.start)..day(&a.time.end),
+                    day(&b.time.start)..day(&b.time.end),
+                );
+                prop_assert!(
+                    a.room != b.room || !share_a_day(&a_stay, &b_stay, turnover),
+                    "Bookings {} and {} overlap",
+                    a.id,
+                    b.id,
+                );
+            }
+        }
+        Ok(())
+    }
+    fn turnover() -> impl Strategy<Value = Turnover> {
+        prop_oneof![Just(Turnover::SameDay), Just(Turnover::NextDay)]
+    }
+
+    proptest! {
+        #[test]
+        fn memory_never_double_books(ops in vec(op(), 0..80), turnover in turnover()) {
+            book_and_cancel(&mut MemoryStorage::default(), turnover, &ops)?;
+        }
+        #[test]
+        fn sqlite_never_double_books(ops in vec(op(), 0..80), turnover in turnover()) {
+            let mut storage = SqliteStorage::open(":memory:".as_ref()).unwrap();
+            book_and_cancel(&mut storage, turnover, &ops)?;
+        }
+    }
+}
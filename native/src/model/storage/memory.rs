@@ -0,0 +1,800 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    mem,
+    ops::{Bound, Range},
+};
+
+use anyhow::{Result, ensure};
+
+use super::{Counters, Storage};
+use crate::data::{
+    Booking, BookingId, Category, Charge, Currency, Customer, CustomerId, Date, ExchangeRate,
+    HotelData, HotelId, PricingRule, PromoCode, RateKind, RatePeriod, RoomData, RoomId, Turnover,
+    fold,
+};
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    counters: Counters,
+    settings: HashMap<String, String>,
+    exchange_rates: HashMap<Currency, ExchangeRate>,
+    categories: HashMap<Category, u32>,
+    hotels: HashMap<HotelId, MemoryHotel>,
+    customers: HashMap<CustomerId, Customer>,
+    /// The charges of each [`fold`]ed city, with the spelling of the city they were added with.
+    charges: HashMap<String, Vec<(String, Charge)>>,
+    promos: HashMap<String, PromoCode>,
+    booking_index: HashMap<BookingId, BookingLocation>,
+    /// How to undo each change since the outermost [`Storage::begin`], oldest first.
+    undo: Vec<Undo>,
+    /// Where in `undo` each group of changes that has not been committed yet starts.
+    savepoints: Vec<usize>,
+}
+type Undo = Box<dyn FnOnce(&mut MemoryStorage) -> Result<()>>;
+struct BookingLocation {
+    hotel: HotelId,
+    room: RoomId,
+    start: Date,
+}
+struct MemoryHotel {
+    data: HotelData,
+    rules: Vec<PricingRule>,
+    rooms: HashMap<RoomId, MemoryRoom>,
+}
+struct MemoryRoom {
+    data: RoomData,
+    rates: Vec<RatePeriod>,
+    bookings: RoomBookings,
+}
+
+/// The bookings of a room, keyed by their start date.
+///
+/// The bookings of a room never overlap, so the only booking that can conflict with a stay is
+/// the last one starting before the stay ends.
+#[derive(Default)]
+struct RoomBookings(BTreeMap<Date, Booking>);
+impl RoomBookings {
+    fn conflicts(&self, time: Range<&Date>, turnover: Turnover) -> bool {
+        self.conflicts_except(time, turnover, None)
+    }
+    fn conflicts_except(
+        &self,
+        time: Range<&Date>,
+        turnover: Turnover,
+        except: Option<BookingId>,
+    ) -> bool {
+        self.0
+            .range((Bound::Unbounded, turnover.start_bound(time.end)))
+            .rev()
+            .find(|(_, booking)| Some(booking.id) != except)
+            .is_some_and(|(_, booking)| turnover.conflicts(&booking.time, time))
+    }
+    fn iter(&self) -> impl Iterator<Item = &Booking> {
+        self.0.values()
+    }
+    fn get(&self, start: &Date) -> Option<&Booking> {
+        self.0.get(start)
+    }
+    fn insert(&mut self, booking: Booking) -> Result<()> {
+        let time = &booking.time.start..&booking.time.end;
+        ensure!(
+            !self.conflicts(time, Turnover::SameDay),
+            "Booking {} overlaps another booking of room {}",
+            booking.id,
+            booking.room,
+        );
+        self.0.insert(booking.time.start.clone(), booking);
+        Ok(())
+    }
+    fn remove(&mut self, start: &Date) -> Option<Booking> {
+        self.0.remove(start)
+    }
+}
+
+impl MemoryStorage {
+    fn rooms_where(
+        &self,
+        mut filter: impl FnMut(&HotelData) -> bool,
+    ) -> Vec<(HotelId, RoomId, RoomData)> {
+        self.hotels
+            .iter()
+            .filter(|(_, hotel)| filter(&hotel.data))
+            .flat_map(|(hi, hotel)| {
+                hotel
+                    .rooms
+                    .iter()
+                    .map(|(ri, room)| (*hi, *ri, room.data.clone()))
+            })
+            .collect()
+    }
+    fn hotel_mut(&mut self, hotel: HotelId) -> Result<&mut MemoryHotel> {
+        self.hotels
+            .get_mut(&hotel)
+            .ok_or_else(|| anyhow::format_err!("Unknown hotel ID {hotel}"))
+    }
+    fn room_mut(&mut self, hotel: HotelId, room: RoomId) -> Result<&mut MemoryRoom> {
+        self.hotels
+            .get_mut(&hotel)
+            .and_then(|hotel| hotel.rooms.get_mut(&room))
+            .ok_or_else(|| anyhow::format_err!("Unknown room ID {room}"))
+    }
+    fn all_bookings(&self) -> impl Iterator<Item = &Booking> {
+        self.hotels
+            .values()
+            .flat_map(|hotel| hotel.rooms.values().flat_map(|room| room.bookings.iter()))
+    }
+    fn index_room(&mut self, hotel: HotelId, id: RoomId, room: &MemoryRoom) {
+        for booking in room.bookings.iter() {
+            let location = BookingLocation {
+                hotel,
+                room: id,
+                start: booking.time.start.clone(),
+            };
+            self.booking_index.insert(booking.id, location);
+        }
+    }
+    fn unindex_room(&mut self, room: &MemoryRoom) {
+        for booking in room.bookings.iter() {
+            self.booking_index.remove(&booking.id);
+        }
+    }
+    fn record(&mut self, undo: impl FnOnce(&mut MemoryStorage) -> Result<()> + 'static) {
+        if !self.savepoints.is_empty() {
+            self.undo.push(Box::new(undo));
+        }
+    }
+}
+
+fn restore<K: Eq + Hash, V>(map: &mut HashMap<K, V>, key: K, old: Option<V>) -> Result<()> {
+    match old {
+        Some(value) => map.insert(key, value),
+        None => map.remove(&key),
+    };
+    Ok(())
+}
+
+impl Storage for MemoryStorage {
+    fn counters(&self) -> Result<Counters> {
+        Ok(self.counters)
+    }
+    fn set_counters(&mut self, counters: Counters) -> Result<()> {
+        let old = mem::replace(&mut self.counters, counters);
+        self.record(move |storage| {
+            storage.counters = old;
+            Ok(())
+        });
+        Ok(())
+    }
+
+    fn exchange_rate(&self, currency: Currency) -> Result<Option<ExchangeRate>> {
+        Ok(self.exchange_rates.get(&currency).cloned())
+    }
+    fn exchange_rates(&self) -> Result<Vec<(Currency, ExchangeRate)>> {
+        Ok(self
+            .exchange_rates
+            .iter()
+            .map(|(currency, rate)| (*currency, rate.clone()))
+            .collect())
+    }
+    fn set_exchange_rates(&mut self, rates: Vec<(Currency, ExchangeRate)>) -> Result<()> {
+        let old = mem::replace(&mut self.exchange_rates, rates.into_iter().collect());
+        self.record(move |storage| {
+            storage.exchange_rates = old;
+            Ok(())
+        });
+        Ok(())
+    }
+
+    fn categories(&self) -> Result<Vec<(Category, u32)>> {
+        Ok(self
+            .categories
+            .iter()
+            .map(|(category, capacity)| (category.clone(), *capacity))
+            .collect())
+    }
+    fn insert_category(&mut self, category: Category, capacity: u32) -> Result<()> {
+        let old = self.categories.insert(category.clone(), capacity);
+        self.record(move |storage| restore(&mut storage.categories, category, old));
+        Ok(())
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.settings.get(key).cloned())
+    }
+    fn settings(&self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .settings
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+    fn set_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        let old = self.settings.insert(key.into(), value.into());
+        let key = key.to_owned();
+        self.record(move |storage| restore(&mut storage.settings, key, old));
+        Ok(())
+    }
+
+    fn hotel(&self, id: HotelId) -> Result<Option<HotelData>> {
+        Ok(self.hotels.get(&id).map(|hotel| hotel.data.clone()))
+    }
+    fn hotels(&self) -> Result<Vec<(HotelId, HotelData)>> {
+        Ok(self
+            .hotels
+            .iter()
+            .map(|(id, hotel)| (*id, hotel.data.clone()))
+            .collect())
+    }
+    fn insert_hotel(&mut self, id: HotelId, data: HotelData) -> Result<()> {
+        self.hotels.insert(
+            id,
+            MemoryHotel {
+                data,
+                rules: Default::default(),
+                rooms: Default::default(),
+            },
+        );
+        self.record(move |storage| {
+            storage.hotels.remove(&id);
+            Ok(())
+        });
+        Ok(())
+    }
+    fn update_hotel(&mut self, id: HotelId, data: HotelData) -> Result<()> {
+        let old = mem::replace(&mut self.hotel_mut(id)?.data, data);
+        self.record(move |storage| {
+            storage.hotel_mut(id)?.data = old;
+            Ok(())
+        });
+        Ok(())
+    }
+    fn remove_hotel(&mut self, id: HotelId) -> Result<bool> {
+        let Some(hotel) = self.hotels.remove(&id) else {
+            return Ok(false);
+        };
+        for room in hotel.rooms.values() {
+            self.unindex_room(room);
+        }
+        self.record(move |storage| {
+            for (room_id, room) in &hotel.rooms {
+                storage.index_room(id, *room_id, room);
+            }
+            storage.hotels.insert(id, hotel);
+            Ok(())
+        });
+        Ok(true)
+    }
+
+    fn rules(&self, hotel: HotelId) -> Result<Vec<PricingRule>> {
+        Ok(self
+            .hotels
+            .get(&hotel)
+            .map(|hotel| hotel.rules.clone())
+            .unwrap_or_default())
+    }
+    fn all_rules(&self) -> Result<Vec<(HotelId, PricingRule)>> {
+        Ok(self
+            .hotels
+            .iter()
+            .flat_map(|(id, hotel)| hotel.rules.iter().map(|rule| (*id, rule.clone())))
+            .collect())
+    }
+    fn insert_rule(&mut self, hotel: HotelId, rule: PricingRule) -> Result<()> {
+        self.hotel_mut(hotel)?.rules.push(rule);
+        self.record(move |storage| {
+            storage.hotel_mut(hotel)?.rules.pop();
+            Ok(())
+        });
+        Ok(())
+    }
+    fn remove_rule(&mut self, hotel: HotelId, name: &str) -> Result<bool> {
+        let rules = &mut self.hotel_mut(hotel)?.rules;
+        let old = rules.clone();
+        rules.retain(|rule| rule.name != name);
+        let removed = rules.len() != old.len();
+        self.record(move |storage| {
+            storage.hotel_mut(hotel)?.rules = old;
+            Ok(())
+        });
+        Ok(removed)
+    }
+
+    fn charges(&self, city: &str) -> Result<Vec<Charge>> {
+        Ok(self
+            .charges
+            .get(&fold(city))
+            .into_iter()
+            .flatten()
+            .map(|(_, charge)| charge.clone())
+            .collect())
+    }
+    fn all_charges(&self) -> Result<Vec<(String, Charge)>> {
+        Ok(self.charges.values().flatten().cloned().collect())
+    }
+    fn insert_charge(&mut self, city: &str, charge: Charge) -> Result<()> {
+        let key = fold(city);
+        let old = self.charges.get(&key).cloned();
+        self.charges
+            .entry(key.clone())
+            .or_default()
+            .push((city.into(), charge));
+        self.record(move |storage| restore(&mut storage.charges, key, old));
+        Ok(())
+    }
+    fn remove_charge(&mut self, city: &str, name: &str) -> Result<bool> {
+        let key = fold(city);
+        let Some(charges) = self.charges.get_mut(&key) else {
+            return Ok(false);
+        };
+        let old = charges.clone();
+        charges.retain(|(_, charge)| charge.name != name);
+        let removed = charges.len() != old.len();
+        self.record(move |storage| restore(&mut storage.charges, key, Some(old)));
+        Ok(removed)
+    }
+
+    fn room(&self, hotel: HotelId, room: RoomId) -> Result<Option<RoomData>> {
+        Ok(self
+            .hotels
+            .get(&hotel)
+            .and_then(|hotel| hotel.rooms.get(&room))
+            .map(|room| room.data.clone()))
+    }
+    fn rooms(&self) -> Result<Vec<(HotelId, RoomId, RoomData)>> {
+        Ok(self.rooms_where(|_| true))
+    }
+    fn rooms_in_city(&self, city: &str) -> Result<Vec<(HotelId, RoomId, RoomData)>> {
+        let city = fold(city);
+        Ok(self.rooms_where(|hotel| fold(&hotel.city) == city))
+    }
+    fn insert_room(&mut self, hotel: HotelId, room: RoomId, data: RoomData) -> Result<()> {
+        self.hotel_mut(hotel)?.rooms.insert(
+            room,
+            MemoryRoom {
+                data,
+                rates: Default::default(),
+                bookings: Default::default(),
+            },
+        );
+        self.record(move |storage| {
+            storage.hotel_mut(hotel)?.rooms.remove(&room);
+            Ok(())
+        });
+        Ok(())
+    }
+    fn update_room(&mut self, hotel: HotelId, room: RoomId, data: RoomData) -> Result<()> {
+        let old = mem::replace(&mut self.room_mut(hotel, room)?.data, data);
+        self.record(move |storage| {
+            storage.room_mut(hotel, room)?.data = old;
+            Ok(())
+        });
+        Ok(())
+    }
+    fn remove_room(&mut self, hotel: HotelId, room: RoomId) -> Result<bool> {
+        let id = room;
+        let Some(room) = self
+            .hotels
+            .get_mut(&hotel)
+            .and_then(|hotel| hotel.rooms.remove(&id))
+        else {
+            return Ok(false);
+        };
+        self.unindex_room(&room);
+        self.record(move |storage| {
+            storage.index_room(hotel, id, &room);
+            storage.hotel_mut(hotel)?.rooms.insert(id, room);
+            Ok(())
+        });
+        Ok(true)
+    }
+
+    fn rates(&self, hotel: HotelId, room: RoomId) -> Result<Vec<RatePeriod>> {
+        Ok(self
+            .hotels
+            .get(&hotel)
+            .and_then(|hotel| hotel.rooms.get(&room))
+            .map(|room| room.rates.clone())
+            .unwrap_or_default())
+    }
+    fn all_rates(&self) -> Result<Vec<(HotelId, RoomId, RatePeriod)>> {
+        Ok(self
+            .hotels
+            .iter()
+            .flat_map(|(hi, hotel)| {
+                hotel.rooms.iter().flat_map(move |(ri, room)| {
+                    room.rates.iter().map(move |rate| (*hi, *ri, rate.clone()))
+                })
+            })
+            .collect())
+    }
+    fn insert_rate(&mut self, hotel: HotelId, room: RoomId, rate: RatePeriod) -> Result<()> {
+        self.room_mut(hotel, room)?.rates.push(rate);
+        self.record(move |storage| {
+            storage.room_mut(hotel, room)?.rates.pop();
+            Ok(())
+        });
+        Ok(())
+    }
+    fn remove_rate(
+        &mut self,
+        hotel: HotelId,
+        room: RoomId,
+        kind: RateKind,
+        start: &Date,
+    ) -> Result<bool> {
+        let rates = &mut self.room_mut(hotel, room)?.rates;
+        let old = rates.clone();
+        rates.retain(|rate| rate.kind != kind || rate.time.start != *start);
+        let removed = rates.len() != old.len();
+        self.record(move |storage| {
+            storage.room_mut(hotel, room)?.rates = old;
+            Ok(())
+        });
+        Ok(removed)
+    }
+
+    fn booking(&self, id: BookingId) -> Result<Option<Booking>> {
+        let Some(BookingLocation { hotel, room, start }) = self.booking_index.get(&id) else {
+            return Ok(None);
+        };
+        Ok(self
+            .hotels
+            .get(hotel)
+            .and_then(|hotel| hotel.rooms.get(room))
+            .and_then(|room| room.bookings.get(start))
+            .cloned())
+    }
+    fn bookings(&self) -> Result<Vec<Booking>> {
+        Ok(self.all_bookings().cloned().collect())
+    }
+    fn bookings_of(&self, hotel: HotelId, room: Option<RoomId>) -> Result<Vec<Booking>> {
+        let Some(hotel) = self.hotels.get(&hotel) else {
+            return Ok(Vec::new());
+        };
+        Ok(hotel
+            .rooms
+            .iter()
+            .filter(|&(id, _)| room.is_none_or(|room| room == *id))
+            .flat_map(|(_, room)| room.bookings.iter().cloned())
+            .collect())
+    }
+    fn is_occupied(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+    ) -> Result<bool> {
+        let Some(room) = self
+            .hotels
+            .get(&hotel)
+            .and_then(|hotel| hotel.rooms.get(&room))
+        else {
+            return Ok(false);
+        };
+        Ok(room.bookings.conflicts(time, turnover))
+    }
+    fn is_occupied_except(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+        except: BookingId,
+    ) -> Result<bool> {
+        let Some(room) = self
+            .hotels
+            .get(&hotel)
+            .and_then(|hotel| hotel.rooms.get(&room))
+        else {
+            return Ok(false);
+        };
+        Ok(room.bookings.conflicts_except(time, turnover, Some(except)))
+    }
+    fn insert_booking(&mut self, booking: Booking) -> Result<()> {
+        let location = BookingLocation {
+            hotel: booking.hotel,
+            room: booking.room,
+            start: booking.time.start.clone(),
+        };
+        let id = booking.id;
+        self.room_mut(booking.hotel, booking.room)?
+            .bookings
+            .insert(booking)?;
+        self.booking_index.insert(id, location);
+        self.record(move |storage| storage.remove_booking(id).map(drop));
+        Ok(())
+    }
+    fn remove_booking(&mut self, id: BookingId) -> Result<bool> {
+        let Some(BookingLocation { hotel, room, start }) = self.booking_index.remove(&id) else {
+            return Ok(false);
+        };
+        let Some(booking) = self
+            .hotels
+            .get_mut(&hotel)
+            .and_then(|hotel| hotel.rooms.get_mut(&room))
+            .and_then(|room| room.bookings.remove(&start))
+        else {
+            return Ok(false);
+        };
+        self.record(move |storage| storage.insert_booking(booking));
+        Ok(true)
+    }
+
+    fn bookings_with_promo(&self, code: &str) -> Result<Vec<Booking>> {
+        Ok(self
+            .all_bookings()
+            .filter(|booking| {
+                booking
+                    .promo
+                    .as_ref()
+                    .is_some_and(|promo| promo.code == code)
+            })
+            .cloned()
+            .collect())
+    }
+    fn bookings_of_customer(&self, customer: CustomerId) -> Result<Vec<Booking>> {
+        Ok(self
+            .all_bookings()
+            .filter(|booking| booking.customer == customer)
+            .cloned()
+            .collect())
+    }
+
+    fn promo(&self, code: &str) -> Result<Option<PromoCode>> {
+        Ok(self.promos.get(code).cloned())
+    }
+    fn promos(&self) -> Result<Vec<PromoCode>> {
+        Ok(self.promos.values().cloned().collect())
+    }
+    fn insert_promo(&mut self, promo: PromoCode) -> Result<()> {
+        let code = promo.code.clone();
+        let old = self.promos.insert(code.clone(), promo);
+        self.record(move |storage| restore(&mut storage.promos, code, old));
+        Ok(())
+    }
+    fn update_promo(&mut self, promo: PromoCode) -> Result<()> {
+        self.insert_promo(promo)
+    }
+
+    fn customer(&self, id: CustomerId) -> Result<Option<Customer>> {
+        Ok(self.customers.get(&id).cloned())
+    }
+    fn customers(&self) -> Result<Vec<(CustomerId, Customer)>> {
+        Ok(self
+            .customers
+            .iter()
+            .map(|(id, customer)| (*id, customer.clone()))
+            .collect())
+    }
+    fn insert_customer(&mut self, id: CustomerId, customer: Customer) -> Result<()> {
+        let old = self.customers.insert(id, customer);
+        self.record(move |storage| restore(&mut storage.customers, id, old));
+        Ok(())
+    }
+    fn update_customer(&mut self, id: CustomerId, customer: Customer) -> Result<()> {
+        self.insert_customer(id, customer)
+    }
+    fn remove_customer(&mut self, id: CustomerId) -> Result<bool> {
+        let Some(customer) = self.customers.remove(&id) else {
+            return Ok(false);
+        };
+        self.record(move |storage| restore(&mut storage.customers, id, Some(customer)));
+        Ok(true)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        let undo = mem::take(&mut self.undo);
+        let savepoints = mem::take(&mut self.savepoints);
+        let old = mem::replace(
+            self,
+            Self {
+                undo,
+                savepoints,
+                ..Self::default()
+            },
+        );
+        self.record(move |storage| {
+            let undo = mem::take(&mut storage.undo);
+            let savepoints = mem::take(&mut storage.savepoints);
+            *storage = Self {
+                undo,
+                savepoints,
+                ..old
+            };
+            Ok(())
+        });
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        self.savepoints.push(self.undo.len());
+        Ok(())
+    }
+    fn commit(&mut self) -> Result<()> {
+        self.savepoints.pop();
+        if self.savepoints.is_empty() {
+            self.undo.clear();
+        }
+        Ok(())
+    }
+    fn rollback(&mut self) -> Result<()> {
+        let start = self.savepoints.pop().unwrap_or_default();
+        let undo = self.undo.split_off(start);
+        // Undoing a change must not record how to redo it
+        let savepoints = mem::take(&mut self.savepoints);
+        let undone = undo.into_iter().rev().try_for_each(|undo| undo(self));
+        self.savepoints = savepoints;
+        undone
+    }
+}
+
+/// Timings of the occupancy checks behind `find available` and `book`, compared with the linear
+/// scan over every booking of a room that [`RoomBookings`] replaced. They take a while, so run
+/// them on their own with `cargo test --release benches -- --ignored --nocapture`.
+#[cfg(test)]
+mod benches {
+    use std::{
+        collections::BTreeSet,
+        hint::black_box,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::data::{HotelDetails, Price};
+
+    const ROOMS: RoomId = 2_000;
+    const BOOKINGS_PER_ROOM: usize = 365;
+    const ROUNDS: u32 = 10;
+
+    fn storage(days: &[Date]) -> (MemoryStorage, Vec<Vec<Booking>>) {
+        let mut storage = MemoryStorage::default();
+        let hotel = HotelId::try_from(1).unwrap();
+        let data = HotelData {
+            city: "Karlsruhe".into(),
+            currency: Currency::EUR,
+            details: HotelDetails::default(),
+        };
+        storage.insert_hotel(hotel, data).unwrap();
+        let price = Price {
+            cents: 100u32.into(),
+            currency: Currency::EUR,
+        };
+        let mut lists = Vec::new();
+        let mut id = 0;
+        for room in 0..ROOMS {
+            let data = RoomData {
+                category: "Single".parse().unwrap(),
+                price: price.clone(),
+                capacity: 1,
+                supplement: None,
+                amenities: BTreeSet::new(),
+            };
+            storage.insert_room(hotel, room, data).unwrap();
+            let mut list = Vec::new();
+            for stay in days.chunks_exact(3).take(BOOKINGS_PER_ROOM) {
+                id += 1;
+                let booking = Booking {
+                    hotel,
+                    room,
+                    time: stay[0].clone()..stay[2].clone(),
+                    customer: 1,
+                    id,
+                    guests: 1,
+                    rate: price.clone(),
+                    total: price.clone(),
+                    promo: None,
+                };
+                list.push(booking.clone());
+                storage.insert_booking(booking).unwrap();
+            }
+            lists.push(list);
+        }
+        (storage, lists)
+    }
+    fn days() -> Vec<Date> {
+        let start: Date = "2030-01-01".parse().unwrap();
+        let end: Date = "2040-01-01".parse().unwrap();
+        // One more day for the end of the free night after the last stay
+        start
+            .nights_until(&end)
+            .take(3 * BOOKINGS_PER_ROOM + 1)
+            .collect()
+    }
+    fn rooms(storage: &MemoryStorage) -> impl Iterator<Item = &MemoryRoom> {
+        storage
+            .hotels
+            .values()
+            .flat_map(|hotel| hotel.rooms.values())
+    }
+    fn rooms_mut(storage: &mut MemoryStorage) -> impl Iterator<Item = &mut MemoryRoom> {
+        storage
+            .hotels
+            .values_mut()
+            .flat_map(|hotel| hotel.rooms.values_mut())
+    }
+    fn linear_conflicts(bookings: &[Booking], time: Range<&Date>, turnover: Turnover) -> bool {
+        bookings
+            .iter()
+            .any(|booking| turnover.conflicts(&booking.time, time.clone()))
+    }
+    fn time<T>(name: &str, mut round: impl FnMut() -> T) -> Duration {
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(round());
+        }
+        let per_round = start.elapsed() / ROUNDS;
+        println!("{name}: {per_round:?}");
+        per_round
+    }
+
+    fn speedup(linear: Duration, indexed: Duration) {
+        println!(
+            "speedup: {:.1}x",
+            linear.as_secs_f64() / indexed.as_secs_f64()
+        );
+        assert!(
+            indexed < linear,
+            "The interval index is slower than a linear scan"
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn find_available() {
+        let days = days();
+        let (storage, lists) = storage(&days);
+        let middle = days.len() / 2 / 3 * 3;
+        // Overlaps the booking that starts on `middle`
+        let stay = &days[middle + 1]..&days[middle + 3];
+        let turnover = Turnover::SameDay;
+        let free = |occupied: &dyn Fn(usize, &MemoryRoom) -> bool| {
+            rooms(&storage)
+                .enumerate()
+                .filter(|&(idx, room)| !occupied(idx, room))
+                .count()
+        };
+        let linear = time("find available, before (linear scan)", || {
+            free(&|idx, _| linear_conflicts(&lists[idx], stay.clone(), turnover))
+        });
+        let indexed = time("find available, after (interval index)", || {
+            free(&|_, room| room.bookings.conflicts(stay.clone(), turnover))
+        });
+        assert_eq!(
+            free(&|_, room| room.bookings.conflicts(stay.clone(), turnover)),
+            free(&|idx, _| linear_conflicts(&lists[idx], stay.clone(), turnover)),
+        );
+        speedup(linear, indexed);
+    }
+
+    #[test]
+    #[ignore]
+    fn book() {
+        let days = days();
+        let (mut storage, mut lists) = storage(&days);
+        let stay = &days[days.len() - 2]..&days[days.len() - 1];
+        let turnover = Turnover::SameDay;
+        let booking = |room: RoomId| Booking {
+            room,
+            time: stay.start.clone()..stay.end.clone(),
+            ..lists[0][0].clone()
+        };
+        let bookings: Vec<_> = (0..ROOMS).map(booking).collect();
+        let linear = time("book, before (linear scan)", || {
+            for (list, booking) in lists.iter_mut().zip(&bookings) {
+                assert!(!linear_conflicts(list, stay.clone(), turnover));
+                list.push(booking.clone());
+                list.pop();
+            }
+        });
+        let indexed = time("book, after (interval index)", || {
+            for (room, booking) in rooms_mut(&mut storage).zip(&bookings) {
+                assert!(!room.bookings.conflicts(stay.clone(), turnover));
+                room.bookings.insert(booking.clone()).unwrap();
+                room.bookings.remove(&booking.time.start);
+            }
+        });
+        speedup(linear, indexed);
+    }
+}
@@ -0,0 +1,987 @@
+use std::{
+    collections::BTreeSet,
+    ops::{Bound, Range},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, ensure};
+use rusqlite::{Connection, OptionalExtension, Params, Row, params};
+
+use super::{Counters, Storage};
+use crate::data::{
+    Amenity, AppliedPromo, Booking, BookingId, Category, Charge, Currency, Customer,
+    CustomerDetails, CustomerId, Date, Discount, ExchangeRate, HotelData, HotelDetails, HotelId,
+    Person, Price, PricingRule, PromoCode, RateKind, RatePeriod, RoomData, RoomId, Turnover, fold,
+};
+
+/// Schema migrations, indexed by the `user_version` they upgrade from.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE counters (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        booking INTEGER NOT NULL,
+        customer INTEGER NOT NULL
+    );
+    INSERT INTO counters (id, booking, customer) VALUES (0, 1, 1);
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE exchange_rates (
+        currency TEXT PRIMARY KEY,
+        rate TEXT NOT NULL
+    );
+    CREATE TABLE categories (
+        name TEXT PRIMARY KEY,
+        capacity INTEGER NOT NULL
+    );
+    CREATE TABLE hotels (
+        id INTEGER PRIMARY KEY,
+        city TEXT NOT NULL,
+        city_key TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        name TEXT,
+        address TEXT,
+        stars TEXT,
+        check_in TEXT,
+        check_out TEXT,
+        contact TEXT
+    );
+    CREATE INDEX hotels_city_key ON hotels (city_key);
+    CREATE TABLE pricing_rules (
+        hotel INTEGER NOT NULL REFERENCES hotels (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        scope TEXT NOT NULL,
+        condition TEXT NOT NULL,
+        adjustment TEXT NOT NULL,
+        PRIMARY KEY (hotel, name)
+    );
+    CREATE TABLE city_charges (
+        city TEXT NOT NULL,
+        city_key TEXT NOT NULL,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        scope TEXT NOT NULL,
+        amount_cents TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        PRIMARY KEY (city_key, name)
+    );
+    CREATE TABLE rooms (
+        hotel INTEGER NOT NULL REFERENCES hotels (id) ON DELETE CASCADE,
+        id INTEGER NOT NULL,
+        category TEXT NOT NULL,
+        price_cents TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        capacity INTEGER NOT NULL,
+        supplement_cents TEXT,
+        amenities TEXT NOT NULL,
+        PRIMARY KEY (hotel, id)
+    );
+    CREATE TABLE room_rates (
+        hotel INTEGER NOT NULL,
+        room INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        start TEXT NOT NULL,
+        end TEXT NOT NULL,
+        price_cents TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        PRIMARY KEY (hotel, room, kind, start),
+        FOREIGN KEY (hotel, room) REFERENCES rooms (hotel, id) ON DELETE CASCADE
+    );
+    CREATE TABLE promo_codes (
+        code TEXT PRIMARY KEY,
+        discount_percent INTEGER,
+        discount_cents TEXT,
+        discount_currency TEXT,
+        start TEXT NOT NULL,
+        end TEXT NOT NULL,
+        usage_limit TEXT NOT NULL,
+        customer_limit TEXT NOT NULL,
+        active INTEGER NOT NULL
+    );
+    CREATE TABLE bookings (
+        id INTEGER PRIMARY KEY,
+        hotel INTEGER NOT NULL,
+        room INTEGER NOT NULL,
+        customer INTEGER NOT NULL,
+        start TEXT NOT NULL,
+        end TEXT NOT NULL,
+        guests INTEGER NOT NULL,
+        rate_cents TEXT NOT NULL,
+        total_cents TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        promo_code TEXT,
+        discount_cents TEXT,
+        FOREIGN KEY (hotel, room) REFERENCES rooms (hotel, id) ON DELETE CASCADE
+    );
+    CREATE INDEX bookings_room ON bookings (hotel, room, start);
+    CREATE INDEX bookings_customer ON bookings (customer);
+    CREATE INDEX bookings_promo ON bookings (promo_code);
+    CREATE TABLE customers (
+        id INTEGER PRIMARY KEY,
+        forename TEXT NOT NULL,
+        surname TEXT NOT NULL,
+        email TEXT,
+        phone TEXT,
+        birth_date TEXT,
+        address TEXT
+    );
+"];
+
+pub struct SqliteStorage {
+    conn: Connection,
+}
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut conn = Connection::open(path)
+            .with_context(|| format!("Cannot open database {}", path.display()))?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        ensure!(
+            version <= MIGRATIONS.len(),
+            "Database version {version} is newer than the supported version {}",
+            MIGRATIONS.len(),
+        );
+        let tx = conn.transaction()?;
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            tx.execute_batch(migration)
+                .with_context(|| format!("Cannot migrate database from version {from}"))?;
+        }
+        tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        tx.commit()?;
+        Ok(Self { conn })
+    }
+
+    fn query<T>(
+        &self,
+        sql: &str,
+        params: impl Params,
+        mut decode: impl FnMut(&Row) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let mut rows = stmt.query(params)?;
+        let mut decoded = Vec::new();
+        while let Some(row) = rows.next()? {
+            decoded.push(decode(row)?);
+        }
+        Ok(decoded)
+    }
+    fn query_one<T>(
+        &self,
+        sql: &str,
+        params: impl Params,
+        decode: impl FnMut(&Row) -> Result<T>,
+    ) -> Result<Option<T>> {
+        Ok(self.query(sql, params, decode)?.into_iter().next())
+    }
+    fn execute(&self, sql: &str, params: impl Params) -> Result<usize> {
+        Ok(self.conn.prepare_cached(sql)?.execute(params)?)
+    }
+    fn occupied(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+        except: Option<BookingId>,
+    ) -> Result<bool> {
+        // See `RoomBookings::conflicts` for why the last booking starting in time suffices
+        let sql = match turnover.start_bound(time.end) {
+            Bound::Excluded(_) => {
+                "SELECT start, end FROM bookings
+                WHERE hotel = ?1 AND room = ?2 AND start < ?3 AND id IS NOT ?4
+                ORDER BY start DESC LIMIT 1"
+            }
+            _ => {
+                "SELECT start, end FROM bookings
+                WHERE hotel = ?1 AND room = ?2 AND start <= ?3 AND id IS NOT ?4
+                ORDER BY start DESC LIMIT 1"
+            }
+        };
+        let last: Option<Range<Date>> = self.query_one(
+            sql,
+            params![hotel.get(), room, time.end.to_string(), except],
+            |row| Ok(parse(row, 0)?..parse(row, 1)?),
+        )?;
+        Ok(last.is_some_and(|last| turnover.conflicts(&last, time)))
+    }
+}
+
+fn parse<T>(row: &Row, idx: usize) -> Result<T>
+where
+    T: FromStr,
+    anyhow::Error: From<T::Err>,
+{
+    Ok(T::from_str(&row.get::<_, String>(idx)?)?)
+}
+fn parse_optional<T>(row: &Row, idx: usize) -> Result<Option<T>>
+where
+    T: FromStr,
+    anyhow::Error: From<T::Err>,
+{
+    Ok(row
+        .get::<_, Option<String>>(idx)?
+        .map(|value| T::from_str(&value))
+        .transpose()?)
+}
+fn decode_price(row: &Row, cents: usize, currency: usize) -> Result<Price> {
+    Ok(Price {
+        cents: parse(row, cents)?,
+        currency: parse(row, currency)?,
+    })
+}
+fn decode_hotel(row: &Row) -> Result<(HotelId, HotelData)> {
+    Ok((
+        HotelId::try_from(row.get::<_, u32>(0)?)?,
+        HotelData {
+            city: row.get(1)?,
+            currency: parse(row, 2)?,
+            details: HotelDetails {
+                name: row.get(3)?,
+                address: row.get(4)?,
+                stars: parse_optional(row, 5)?,
+                check_in: parse_optional(row, 6)?,
+                check_out: parse_optional(row, 7)?,
+                contact: row.get(8)?,
+            },
+        },
+    ))
+}
+/// The columns of a hotel in the order that [`decode_hotel`] reads them.
+fn encode_hotel(id: HotelId, hotel: HotelData) -> impl Params {
+    let HotelDetails {
+        name,
+        address,
+        stars,
+        check_in,
+        check_out,
+        contact,
+    } = hotel.details;
+    (
+        id.get(),
+        fold(&hotel.city),
+        hotel.city,
+        hotel.currency.to_string(),
+        name,
+        address,
+        stars.map(|stars| stars.to_string()),
+        check_in.map(|time| time.to_string()),
+        check_out.map(|time| time.to_string()),
+        contact,
+    )
+}
+fn decode_room(row: &Row) -> Result<(HotelId, RoomId, RoomData)> {
+    Ok((
+        HotelId::try_from(row.get::<_, u32>(0)?)?,
+        row.get(1)?,
+        RoomData {
+            category: parse(row, 2)?,
+            price: decode_price(row, 3, 4)?,
+            capacity: row.get(5)?,
+            // Supplements are in the currency of the room
+            supplement: match row.get::<_, Option<String>>(6)? {
+                Some(_) => Some(decode_price(row, 6, 4)?),
+                None => None,
+            },
+            amenities: row
+                .get::<_, String>(7)?
+                .split_whitespace()
+                .map(Amenity::from_str)
+                .collect::<Result<_>>()?,
+        },
+    ))
+}
+fn encode_amenities(amenities: &BTreeSet<Amenity>) -> String {
+    let amenities: Vec<_> = amenities.iter().map(Amenity::to_string).collect();
+    amenities.join(" ")
+}
+fn decode_booking(row: &Row) -> Result<Booking> {
+    Ok(Booking {
+        id: row.get(0)?,
+        hotel: HotelId::try_from(row.get::<_, u32>(1)?)?,
+        room: row.get(2)?,
+        customer: row.get(3)?,
+        time: parse(row, 4)?..parse(row, 5)?,
+        guests: row.get(11)?,
+        rate: decode_price(row, 6, 10)?,
+        total: decode_price(row, 7, 10)?,
+        promo: match row.get::<_, Option<String>>(8)? {
+            Some(code) => Some(AppliedPromo {
+                code,
+                discount: decode_price(row, 9, 10)?,
+            }),
+            None => None,
+        },
+    })
+}
+fn decode_promo(row: &Row) -> Result<PromoCode> {
+    Ok(PromoCode {
+        code: row.get(0)?,
+        discount: match row.get(1)? {
+            Some(percent) => Discount::Percent(percent),
+            None => Discount::Fixed(decode_price(row, 2, 8)?),
+        },
+        valid: parse(row, 3)?..parse(row, 4)?,
+        limit: parse(row, 5)?,
+        customer_limit: parse(row, 6)?,
+        active: row.get(7)?,
+    })
+}
+fn encode_discount(discount: &Discount) -> (Option<u32>, Option<String>, Option<String>) {
+    match discount {
+        Discount::Percent(percent) => (Some(*percent), None, None),
+        Discount::Fixed(price) => (
+            None,
+            Some(price.cents.to_string()),
+            Some(price.currency.to_string()),
+        ),
+    }
+}
+fn decode_rate(row: &Row) -> Result<(HotelId, RoomId, RatePeriod)> {
+    Ok((
+        HotelId::try_from(row.get::<_, u32>(0)?)?,
+        row.get(1)?,
+        RatePeriod {
+            kind: parse(row, 2)?,
+            time: parse(row, 3)?..parse(row, 4)?,
+            price: decode_price(row, 5, 6)?,
+        },
+    ))
+}
+fn decode_rule(row: &Row) -> Result<(HotelId, PricingRule)> {
+    Ok((
+        HotelId::try_from(row.get::<_, u32>(0)?)?,
+        PricingRule {
+            name: row.get(1)?,
+            scope: parse(row, 2)?,
+            condition: parse(row, 3)?,
+            adjustment: parse(row, 4)?,
+        },
+    ))
+}
+fn decode_charge(row: &Row) -> Result<(String, Charge)> {
+    Ok((
+        row.get(0)?,
+        Charge {
+            name: row.get(1)?,
+            kind: parse(row, 2)?,
+            scope: parse(row, 3)?,
+            amount: decode_price(row, 4, 5)?,
+        },
+    ))
+}
+fn decode_customer(row: &Row) -> Result<(CustomerId, Customer)> {
+    Ok((
+        row.get(0)?,
+        Customer {
+            person: Person {
+                forename: row.get(1)?,
+                surname: row.get(2)?,
+            },
+            details: CustomerDetails {
+                email: row.get(3)?,
+                phone: row.get(4)?,
+                birth_date: parse_optional(row, 5)?,
+                address: row.get(6)?,
+            },
+        },
+    ))
+}
+/// The columns of a customer in the order that [`decode_customer`] reads them.
+fn encode_customer(id: CustomerId, customer: Customer) -> impl Params {
+    let Customer {
+        person: Person { forename, surname },
+        details:
+            CustomerDetails {
+                email,
+                phone,
+                birth_date,
+                address,
+            },
+    } = customer;
+    (
+        id,
+        forename,
+        surname,
+        email,
+        phone,
+        birth_date.map(|date| date.to_string()),
+        address,
+    )
+}
+
+impl Storage for SqliteStorage {
+    fn counters(&self) -> Result<Counters> {
+        Ok(self.conn.query_row(
+            "SELECT booking, customer FROM counters WHERE id = 0",
+            [],
+            |row| {
+                Ok(Counters {
+                    booking: row.get(0)?,
+                    customer: row.get(1)?,
+                })
+            },
+        )?)
+    }
+    fn set_counters(&mut self, counters: Counters) -> Result<()> {
+        self.execute(
+            "UPDATE counters SET booking = ?1, customer = ?2 WHERE id = 0",
+            params![counters.booking, counters.customer],
+        )?;
+        Ok(())
+    }
+
+    fn exchange_rate(&self, currency: Currency) -> Result<Option<ExchangeRate>> {
+        self.query_one(
+            "SELECT rate FROM exchange_rates WHERE currency = ?1",
+            [currency.to_string()],
+            |row| parse(row, 0),
+        )
+    }
+    fn exchange_rates(&self) -> Result<Vec<(Currency, ExchangeRate)>> {
+        self.query("SELECT currency, rate FROM exchange_rates", [], |row| {
+            Ok((parse(row, 0)?, parse(row, 1)?))
+        })
+    }
+    fn set_exchange_rates(&mut self, rates: Vec<(Currency, ExchangeRate)>) -> Result<()> {
+        self.execute("DELETE FROM exchange_rates", [])?;
+        for (currency, rate) in rates {
+            self.execute(
+                "INSERT INTO exchange_rates (currency, rate) VALUES (?1, ?2)",
+                [currency.to_string(), rate.to_string()],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn categories(&self) -> Result<Vec<(Category, u32)>> {
+        self.query("SELECT name, capacity FROM categories", [], |row| {
+            Ok((parse(row, 0)?, row.get(1)?))
+        })
+    }
+    fn insert_category(&mut self, category: Category, capacity: u32) -> Result<()> {
+        self.execute(
+            "INSERT INTO categories (name, capacity) VALUES (?1, ?2)",
+            params![category.as_str(), capacity],
+        )?;
+        Ok(())
+    }
+
+    fn setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+    fn settings(&self) -> Result<Vec<(String, String)>> {
+        self.query("SELECT key, value FROM settings", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+    }
+    fn set_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        self.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    fn hotel(&self, id: HotelId) -> Result<Option<HotelData>> {
+        Ok(self
+            .query_one(
+                "SELECT id, city, currency, name, address, stars, check_in, check_out, contact
+                FROM hotels WHERE id = ?1",
+                [id.get()],
+                decode_hotel,
+            )?
+            .map(|(_, hotel)| hotel))
+    }
+    fn hotels(&self) -> Result<Vec<(HotelId, HotelData)>> {
+        self.query(
+            "SELECT id, city, currency, name, address, stars, check_in, check_out, contact
+            FROM hotels",
+            [],
+            decode_hotel,
+        )
+    }
+    fn insert_hotel(&mut self, id: HotelId, hotel: HotelData) -> Result<()> {
+        self.execute(
+            "INSERT INTO hotels (
+                id, city_key, city, currency, name, address, stars, check_in, check_out, contact
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            encode_hotel(id, hotel),
+        )?;
+        Ok(())
+    }
+    fn update_hotel(&mut self, id: HotelId, hotel: HotelData) -> Result<()> {
+        self.execute(
+            "UPDATE hotels
+            SET city_key = ?2, city = ?3, currency = ?4, name = ?5, address = ?6, stars = ?7,
+                check_in = ?8, check_out = ?9, contact = ?10
+            WHERE id = ?1",
+            encode_hotel(id, hotel),
+        )?;
+        Ok(())
+    }
+    fn remove_hotel(&mut self, id: HotelId) -> Result<bool> {
+        Ok(self.execute("DELETE FROM hotels WHERE id = ?1", [id.get()])? != 0)
+    }
+
+    fn rules(&self, hotel: HotelId) -> Result<Vec<PricingRule>> {
+        Ok(self
+            .query(
+                "SELECT hotel, name, scope, condition, adjustment FROM pricing_rules
+                WHERE hotel = ?1",
+                [hotel.get()],
+                decode_rule,
+            )?
+            .into_iter()
+            .map(|(_, rule)| rule)
+            .collect())
+    }
+    fn all_rules(&self) -> Result<Vec<(HotelId, PricingRule)>> {
+        self.query(
+            "SELECT hotel, name, scope, condition, adjustment FROM pricing_rules",
+            [],
+            decode_rule,
+        )
+    }
+    fn insert_rule(&mut self, hotel: HotelId, rule: PricingRule) -> Result<()> {
+        self.execute(
+            "INSERT INTO pricing_rules (hotel, name, scope, condition, adjustment)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                hotel.get(),
+                rule.name,
+                rule.scope.to_string(),
+                rule.condition.to_string(),
+                rule.adjustment.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+    fn remove_rule(&mut self, hotel: HotelId, name: &str) -> Result<bool> {
+        Ok(self.execute(
+            "DELETE FROM pricing_rules WHERE hotel = ?1 AND name = ?2",
+            params![hotel.get(), name],
+        )? != 0)
+    }
+
+    fn charges(&self, city: &str) -> Result<Vec<Charge>> {
+        Ok(self
+            .query(
+                "SELECT city, name, kind, scope, amount_cents, currency FROM city_charges
+                WHERE city_key = ?1",
+                [fold(city)],
+                decode_charge,
+            )?
+            .into_iter()
+            .map(|(_, charge)| charge)
+            .collect())
+    }
+    fn all_charges(&self) -> Result<Vec<(String, Charge)>> {
+        self.query(
+            "SELECT city, name, kind, scope, amount_cents, currency FROM city_charges",
+            [],
+            decode_charge,
+        )
+    }
+    fn insert_charge(&mut self, city: &str, charge: Charge) -> Result<()> {
+        self.execute(
+            "INSERT INTO city_charges (city_key, city, name, kind, scope, amount_cents, currency)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                fold(city),
+                city,
+                charge.name,
+                charge.kind.to_string(),
+                charge.scope.to_string(),
+                charge.amount.cents.to_string(),
+                charge.amount.currency.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+    fn remove_charge(&mut self, city: &str, name: &str) -> Result<bool> {
+        Ok(self.execute(
+            "DELETE FROM city_charges WHERE city_key = ?1 AND name = ?2",
+            [&*fold(city), name],
+        )? != 0)
+    }
+
+    fn room(&self, hotel: HotelId, room: RoomId) -> Result<Option<RoomData>> {
+        Ok(self
+            .query_one(
+                "SELECT hotel, id, category, price_cents, currency, capacity, supplement_cents,
+                    amenities
+                FROM rooms WHERE hotel = ?1 AND id = ?2",
+                params![hotel.get(), room],
+                decode_room,
+            )?
+            .map(|(_, _, room)| room))
+    }
+    fn rooms(&self) -> Result<Vec<(HotelId, RoomId, RoomData)>> {
+        self.query(
+            "SELECT hotel, id, category, price_cents, currency, capacity, supplement_cents,
+                amenities
+            FROM rooms",
+            [],
+            decode_room,
+        )
+    }
+    fn rooms_in_city(&self, city: &str) -> Result<Vec<(HotelId, RoomId, RoomData)>> {
+        self.query(
+            "SELECT r.hotel, r.id, r.category, r.price_cents, r.currency, r.capacity,
+                r.supplement_cents, r.amenities
+            FROM rooms r JOIN hotels h ON h.id = r.hotel
+            WHERE h.city_key = ?1",
+            [fold(city)],
+            decode_room,
+        )
+    }
+    fn insert_room(&mut self, hotel: HotelId, room: RoomId, data: RoomData) -> Result<()> {
+        self.execute(
+            "INSERT INTO rooms (
+                hotel, id, category, price_cents, currency, capacity, supplement_cents, amenities
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                hotel.get(),
+                room,
+                data.category.to_string(),
+                data.price.cents.to_string(),
+                data.price.currency.to_string(),
+                data.capacity,
+                data.supplement.map(|price| price.cents.to_string()),
+                encode_amenities(&data.amenities),
+            ],
+        )?;
+        Ok(())
+    }
+    fn update_room(&mut self, hotel: HotelId, room: RoomId, data: RoomData) -> Result<()> {
+        self.execute(
+            "UPDATE rooms
+            SET category = ?3, price_cents = ?4, currency = ?5, capacity = ?6,
+                supplement_cents = ?7, amenities = ?8
+            WHERE hotel = ?1 AND id = ?2",
+            params![
+                hotel.get(),
+                room,
+                data.category.to_string(),
+                data.price.cents.to_string(),
+                data.price.currency.to_string(),
+                data.capacity,
+                data.supplement.map(|price| price.cents.to_string()),
+                encode_amenities(&data.amenities),
+            ],
+        )?;
+        Ok(())
+    }
+    fn remove_room(&mut self, hotel: HotelId, room: RoomId) -> Result<bool> {
+        Ok(self.execute(
+            "DELETE FROM rooms WHERE hotel = ?1 AND id = ?2",
+            params![hotel.get(), room],
+        )? != 0)
+    }
+
+    fn rates(&self, hotel: HotelId, room: RoomId) -> Result<Vec<RatePeriod>> {
+        Ok(self
+            .query(
+                "SELECT hotel, room, kind, start, end, price_cents, currency FROM room_rates
+                WHERE hotel = ?1 AND room = ?2",
+                params![hotel.get(), room],
+                decode_rate,
+            )?
+            .into_iter()
+            .map(|(_, _, rate)| rate)
+            .collect())
+    }
+    fn all_rates(&self) -> Result<Vec<(HotelId, RoomId, RatePeriod)>> {
+        self.query(
+            "SELECT hotel, room, kind, start, end, price_cents, currency FROM room_rates",
+            [],
+            decode_rate,
+        )
+    }
+    fn insert_rate(&mut self, hotel: HotelId, room: RoomId, rate: RatePeriod) -> Result<()> {
+        let RatePeriod {
+            kind,
+            time: Range { start, end },
+            price,
+        } = rate;
+        self.execute(
+            "INSERT INTO room_rates (hotel, room, kind, start, end, price_cents, currency)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                hotel.get(),
+                room,
+                kind.to_string(),
+                start.to_string(),
+                end.to_string(),
+                price.cents.to_string(),
+                price.currency.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+    fn remove_rate(
+        &mut self,
+        hotel: HotelId,
+        room: RoomId,
+        kind: RateKind,
+        start: &Date,
+    ) -> Result<bool> {
+        Ok(self.execute(
+            "DELETE FROM room_rates WHERE hotel = ?1 AND room = ?2 AND kind = ?3 AND start = ?4",
+            params![hotel.get(), room, kind.to_string(), start.to_string()],
+        )? != 0)
+    }
+
+    fn booking(&self, id: BookingId) -> Result<Option<Booking>> {
+        self.query_one(
+            "SELECT id, hotel, room, customer, start, end, rate_cents, total_cents, promo_code,
+                discount_cents, currency, guests
+            FROM bookings WHERE id = ?1",
+            [id],
+            decode_booking,
+        )
+    }
+    fn bookings(&self) -> Result<Vec<Booking>> {
+        self.query(
+            "SELECT id, hotel, room, customer, start, end, rate_cents, total_cents, promo_code,
+                discount_cents, currency, guests
+            FROM bookings",
+            [],
+            decode_booking,
+        )
+    }
+    fn bookings_of(&self, hotel: HotelId, room: Option<RoomId>) -> Result<Vec<Booking>> {
+        self.query(
+            "SELECT id, hotel, room, customer, start, end, rate_cents, total_cents, promo_code,
+                discount_cents, currency, guests
+            FROM bookings
+            WHERE hotel = ?1 AND (?2 IS NULL OR room = ?2)",
+            params![hotel.get(), room],
+            decode_booking,
+        )
+    }
+    fn is_occupied(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+    ) -> Result<bool> {
+        self.occupied(hotel, room, time, turnover, None)
+    }
+    fn is_occupied_except(
+        &self,
+        hotel: HotelId,
+        room: RoomId,
+        time: Range<&Date>,
+        turnover: Turnover,
+        except: BookingId,
+    ) -> Result<bool> {
+        self.occupied(hotel, room, time, turnover, Some(except))
+    }
+    fn insert_booking(&mut self, booking: Booking) -> Result<()> {
+        let Booking {
+            hotel,
+            room,
+            time: Range { start, end },
+            customer,
+            id,
+            guests,
+            rate,
+            total,
+            promo,
+        } = booking;
+        ensure!(
+            !self.is_occupied(hotel, room, &start..&end, Turnover::SameDay)?,
+            "Booking {id} overlaps another booking of room {room}",
+        );
+        let (promo_code, discount_cents) = match promo {
+            Some(AppliedPromo { code, discount }) => (Some(code), Some(discount.cents.to_string())),
+            None => (None, None),
+        };
+        self.execute(
+            "INSERT INTO bookings (
+                id, hotel, room, customer, start, end, rate_cents, total_cents, promo_code,
+                discount_cents, currency, guests
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                id,
+                hotel.get(),
+                room,
+                customer,
+                start.to_string(),
+                end.to_string(),
+                rate.cents.to_string(),
+                total.cents.to_string(),
+                promo_code,
+                discount_cents,
+                rate.currency.to_string(),
+                guests,
+            ],
+        )?;
+        Ok(())
+    }
+    fn remove_booking(&mut self, id: BookingId) -> Result<bool> {
+        Ok(self.execute("DELETE FROM bookings WHERE id = ?1", [id])? != 0)
+    }
+
+    fn bookings_with_promo(&self, code: &str) -> Result<Vec<Booking>> {
+        self.query(
+            "SELECT id, hotel, room, customer, start, end, rate_cents, total_cents, promo_code,
+                discount_cents, currency, guests
+            FROM bookings WHERE promo_code = ?1",
+            [code],
+            decode_booking,
+        )
+    }
+    fn bookings_of_customer(&self, customer: CustomerId) -> Result<Vec<Booking>> {
+        self.query(
+            "SELECT id, hotel, room, customer, start, end, rate_cents, total_cents, promo_code,
+                discount_cents, currency, guests
+            FROM bookings WHERE customer = ?1",
+            [customer],
+            decode_booking,
+        )
+    }
+
+    fn promo(&self, code: &str) -> Result<Option<PromoCode>> {
+        self.query_one(
+            "SELECT code, discount_percent, discount_cents, start, end, usage_limit,
+                customer_limit, active, discount_currency
+            FROM promo_codes WHERE code = ?1",
+            [code],
+            decode_promo,
+        )
+    }
+    fn promos(&self) -> Result<Vec<PromoCode>> {
+        self.query(
+            "SELECT code, discount_percent, discount_cents, start, end, usage_limit,
+                customer_limit, active, discount_currency
+            FROM promo_codes",
+            [],
+            decode_promo,
+        )
+    }
+    fn insert_promo(&mut self, promo: PromoCode) -> Result<()> {
+        let (percent, cents, currency) = encode_discount(&promo.discount);
+        self.execute(
+            "INSERT INTO promo_codes (
+                code, discount_percent, discount_cents, start, end, usage_limit, customer_limit,
+                active, discount_currency
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                promo.code,
+                percent,
+                cents,
+                promo.valid.start.to_string(),
+                promo.valid.end.to_string(),
+                promo.limit.to_string(),
+                promo.customer_limit.to_string(),
+                promo.active,
+                currency,
+            ],
+        )?;
+        Ok(())
+    }
+    fn update_promo(&mut self, promo: PromoCode) -> Result<()> {
+        let (percent, cents, currency) = encode_discount(&promo.discount);
+        self.execute(
+            "UPDATE promo_codes
+            SET discount_percent = ?2, discount_cents = ?3, start = ?4, end = ?5,
+                usage_limit = ?6, customer_limit = ?7, active = ?8, discount_currency = ?9
+            WHERE code = ?1",
+            params![
+                promo.code,
+                percent,
+                cents,
+                promo.valid.start.to_string(),
+                promo.valid.end.to_string(),
+                promo.limit.to_string(),
+                promo.customer_limit.to_string(),
+                promo.active,
+                currency,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn customer(&self, id: CustomerId) -> Result<Option<Customer>> {
+        Ok(self
+            .query_one(
+                "SELECT id, forename, surname, email, phone, birth_date, address FROM customers
+                WHERE id = ?1",
+                [id],
+                decode_customer,
+            )?
+            .map(|(_, customer)| customer))
+    }
+    fn customers(&self) -> Result<Vec<(CustomerId, Customer)>> {
+        self.query(
+            "SELECT id, forename, surname, email, phone, birth_date, address FROM customers",
+            [],
+            decode_customer,
+        )
+    }
+    fn insert_customer(&mut self, id: CustomerId, customer: Customer) -> Result<()> {
+        self.execute(
+            "INSERT INTO customers (id, forename, surname, email, phone, birth_date, address)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            encode_customer(id, customer),
+        )?;
+        Ok(())
+    }
+    fn update_customer(&mut self, id: CustomerId, customer: Customer) -> Result<()> {
+        self.execute(
+            "UPDATE customers
+            SET forename = ?2, surname = ?3, email = ?4, phone = ?5, birth_date = ?6, address = ?7
+            WHERE id = ?1",
+            encode_customer(id, customer),
+        )?;
+        Ok(())
+    }
+    fn remove_customer(&mut self, id: CustomerId) -> Result<bool> {
+        Ok(self.execute("DELETE FROM customers WHERE id = ?1", [id])? != 0)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.conn.execute_batch(
+            "DELETE FROM bookings;
+            DELETE FROM room_rates;
+            DELETE FROM rooms;
+            DELETE FROM pricing_rules;
+            DELETE FROM hotels;
+            DELETE FROM customers;
+            DELETE FROM promo_codes;
+            DELETE FROM city_charges;
+            DELETE FROM exchange_rates;
+            DELETE FROM categories;
+            DELETE FROM settings;",
+        )?;
+        self.set_counters(Counters::default())
+    }
+
+    // Savepoints nest, unlike plain transactions
+    fn begin(&mut self) -> Result<()> {
+        Ok(self.conn.execute_batch("SAVEPOINT storage")?)
+    }
+    fn commit(&mut self) -> Result<()> {
+        Ok(self.conn.execute_batch("RELEASE storage")?)
+    }
+    fn rollback(&mut self) -> Result<()> {
+        Ok(self
+            .conn
+            .execute_batch("ROLLBACK TO storage; RELEASE storage")?)
+    }
+}